@@ -1,7 +1,9 @@
 extern crate serde;
 extern crate serde_yaml;
 
+use crate::utils;
 use crate::utils::Args;
+use crate::validation;
 use crate::weights::StatShift;
 use std::fs;
 use std::collections::HashMap;
@@ -10,10 +12,16 @@ use serde::{Serialize, Deserialize};
 const ARMOR_COLLECTION_PATH: &str = "db/armor.yaml";
 const ENCHANT_COLLECTION_PATH: &str = "db/enchants.yaml";
 const SET_BONUSES_COLLECTION_PATH: &str = "db/set_bonuses.yaml";
+const TALENT_COLLECTION_PATH: &str = "db/talents.yaml";
 const WEAPON_COLLECTION_PATH: &str = "db/weapons.yaml";
+// talent points of a level 60 character
+pub const TALENT_POINTS: i32 = 51;
+// points spent in a tree that unlock each further row
+const POINTS_PER_ROW: i32 = 5;
+pub const TALENT_TREES: [&str; 3] = ["Assassination", "Combat", "Subtlety"];
 
 
-#[derive(Debug,Serialize,Deserialize)]
+#[derive(Clone,Debug,Serialize,Deserialize)]
 struct Buffs {
     motw: bool,
     bom: bool,
@@ -42,6 +50,47 @@ impl Buffs {
     }
 }
 
+#[derive(Clone,Debug,Serialize,Deserialize)]
+#[serde(default)]
+pub struct Debuffs {
+    pub sunder_armor: bool,
+    pub curse_of_recklessness: bool,
+    pub faerie_fire: bool
+}
+
+impl Default for Debuffs {
+    fn default() -> Debuffs {
+        Debuffs {
+            sunder_armor: true,
+            curse_of_recklessness: true,
+            faerie_fire: true
+        }
+    }
+}
+
+#[derive(Clone,Debug,Serialize,Deserialize)]
+#[serde(default)]
+pub struct Target {
+    pub level: i32,
+    pub armor: i32,
+    pub debuffs: Debuffs,
+    pub behind: bool, // parry, block and no backstab when in front
+    pub block_value: f32
+}
+
+impl Default for Target {
+    // a level 63 raid boss with all armor debuffs, attacked from behind
+    fn default() -> Target {
+        Target {
+            level: 63,
+            armor: 3731,
+            debuffs: Debuffs::default(),
+            behind: true,
+            block_value: 0.0
+        }
+    }
+}
+
 #[derive(Clone,Copy,Debug,PartialEq,Serialize,Deserialize)]
 pub enum Race {
     Human,
@@ -183,7 +232,6 @@ pub struct Weapon {
     swing_interval: f32,
     min_dmg: f32,
     max_dmg: f32,
-    mean_dmg: f32,
     hit_procc: HitProcc
 }
 
@@ -200,7 +248,6 @@ impl Weapon {
             swing_interval: 0.0,
             min_dmg: 0.0,
             max_dmg: 0.0,
-            mean_dmg: 0.0,
             hit_procc: HitProcc::None
         }
     }
@@ -217,17 +264,28 @@ impl Weapon {
             swing_interval: self.swing_interval,
             min_dmg: self.min_dmg,
             max_dmg: self.max_dmg,
-            mean_dmg: self.mean_dmg,
             hit_procc: self.hit_procc.clone()
         }
     }
 
-    fn set_mean_dmg(&mut self) {
-        self.mean_dmg = (self.min_dmg + self.max_dmg) / 2.0;
+    pub fn get_name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn get_min_dmg(&self) -> f32 {
+        return self.min_dmg;
+    }
+
+    pub fn get_max_dmg(&self) -> f32 {
+        return self.max_dmg;
+    }
+
+    pub fn fits_slot(&self, slot: Slot) -> bool {
+        return self.slot.contains(&slot);
     }
 
-    pub fn get_mean_dmg(&self) -> f32 {
-        return self.mean_dmg;
+    pub fn is_unique(&self) -> bool {
+        return self.unique;
     }
 
     pub fn get_swing_interval(&self) -> f32 {
@@ -243,8 +301,8 @@ impl Weapon {
     }
 }
 
-#[derive(Debug,Clone,Serialize,Deserialize)]
-enum Slot {
+#[derive(Clone,Copy,Debug,PartialEq,Serialize,Deserialize)]
+pub enum Slot {
     Head,
     Neck,
     Shoulders,
@@ -266,10 +324,10 @@ enum Slot {
 #[derive(Debug,Serialize,Deserialize)]
 pub struct Armor {
     name: String,
-    set_tag: String,
-    slot: Slot,
-    prim_stats: PrimStats,
-    sec_stats: SecStats,
+    pub set_tag: String,
+    pub slot: Slot,
+    pub prim_stats: PrimStats,
+    pub sec_stats: SecStats,
     pub hit_procc: HitProcc
 }
 
@@ -278,53 +336,92 @@ impl Armor {
         Armor {
             name: self.name.to_string(),
             set_tag: self.set_tag.to_string(),
-            slot: self.slot.clone(),
+            slot: self.slot,
             prim_stats: self.prim_stats.clone(),
             sec_stats: self.sec_stats.clone(),
             hit_procc: self.hit_procc.clone()
         }
     }
+
+    pub fn get_name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn get_slot_name(&self) -> String {
+        return format!("{:?}", self.slot);
+    }
 }
 
-#[derive(Debug,Serialize,Deserialize)]
-struct CharacterSpecification {
-    items: ItemSpecification,
-    enchants: EnchantSpecification,
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct CharacterSpecification {
+    pub items: ItemSpecification,
+    pub enchants: EnchantSpecification,
     buffs: Buffs,
-    talents: Talents
+    pub talents: Talents,
+    #[serde(default)]
+    pub target: Target
 }
 
 impl CharacterSpecification {
+    pub fn new() -> CharacterSpecification {
+        // no items or enchants, everything else at its default
+        CharacterSpecification {
+            items: ItemSpecification {
+                mh_name: "".to_string(),
+                oh_name: "".to_string(),
+                armor_names: Vec::new()
+            },
+            enchants: EnchantSpecification {
+                armor_enchant_names: Vec::new(),
+                mh_enchant_names: Vec::new(),
+                oh_enchant_names: Vec::new()
+            },
+            buffs: Buffs::new(),
+            talents: Talents::new(),
+            target: Target::default()
+        }
+    }
+
     pub fn get_char_spec(args: &Args) -> CharacterSpecification {
+        // every problem of the file is reported before anything simulates
+        match validation::get_valid_spec(&args.spec_file) {
+            Ok(character_spec) => return character_spec,
+            Err(problems) => {
+                eprintln!("Found {} problem(s) in {}:", problems.len(),
+                          args.spec_file);
+                for problem in problems { eprintln!("{}", problem); }
+                std::process::exit(1);
+            }
+        }
+    }
 
-        let character_spec_string = fs::read_to_string(&args.spec_file)
-            .expect(&format!("Something went wrong items from {}.",
-                    args.spec_file));
-        let character_spec: CharacterSpecification = serde_yaml::from_str(
-            &character_spec_string).unwrap();
-        return character_spec;
+    pub fn write_to_file(&self, path: &str, header: &str) 
+        -> std::io::Result<()> {
+        let yaml = serde_yaml::to_string(self)
+            .map_err(std::io::Error::other)?;
+        return fs::write(path, format!("# {}\n{}", header, yaml));
     }
 }
 
-#[derive(Debug,Serialize,Deserialize)]
-struct ItemSpecification {
-    mh_name: String,
-    oh_name: String,
-    armor_names: Vec<String>
+#[derive(Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct ItemSpecification {
+    pub mh_name: String,
+    pub oh_name: String,
+    pub armor_names: Vec<String>
 }
 
-#[derive(Debug,Serialize,Deserialize)]
-struct EnchantSpecification {
-    armor_enchant_names: Vec<String>,
-    mh_enchant_names: Vec<String>,
-    oh_enchant_names: Vec<String>
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct EnchantSpecification {
+    pub armor_enchant_names: Vec<String>,
+    pub mh_enchant_names: Vec<String>,
+    pub oh_enchant_names: Vec<String>
 }
 
 #[derive(Debug,Serialize,Deserialize)]
 pub struct Enchant {
     pub name: String,
-    slot: Vec<Slot>,
-    enchant_type: EnchantType,
+    pub slot: Vec<Slot>,
+    pub enchant_type: EnchantType,
     prim_stats: PrimStats,
     sec_stats: SecStats,
     pub hit_procc: HitProcc,
@@ -335,7 +432,7 @@ impl Enchant {
     fn copy(&self) -> Enchant {
         Enchant {
             name: self.name.to_string(),
-            enchant_type: self.enchant_type.clone(),
+            enchant_type: self.enchant_type,
             slot: self.slot.clone(),
             prim_stats: self.prim_stats.clone(),
             sec_stats: self.sec_stats.clone(),
@@ -345,7 +442,7 @@ impl Enchant {
     }
 }
 
-#[derive(Debug,Serialize,Deserialize)]
+#[derive(Clone,Debug,Serialize,Deserialize)]
 pub struct Talents {
     // assassination
     pub improved_eviscerate: i32,
@@ -367,7 +464,160 @@ pub struct Talents {
     pub opportunity: i32
 }
 
+#[derive(Debug,Serialize,Deserialize)]
+pub struct TalentInfo {
+    pub name: String,
+    pub max_points: i32,
+    pub tree: String,
+    pub row: i32,
+    #[serde(default)]
+    pub requires: String,
+    // no effect in the simulator, only its points count
+    #[serde(default)]
+    pub filler: bool
+}
+
+pub fn get_talent_collection() -> HashMap<String,TalentInfo> {
+    let talent_string = fs::read_to_string(TALENT_COLLECTION_PATH)
+            .expect("Something went wrong reading talents from file.");
+    let talents: HashMap<String,TalentInfo> = serde_yaml::from_str(
+        &talent_string).expect("Could not parse talent db");
+    return talents;
+}
+
+pub fn get_tree_index(tree: &str) -> usize {
+    return TALENT_TREES.iter().position(|name| *name == tree)
+        .unwrap_or_else(|| panic!("Unknown talent tree {}", tree));
+}
+
+pub fn get_searched_talents(talent_db: &HashMap<String,TalentInfo>,
+                        talents: &Talents) -> Vec<String> {
+    // talents the simulator implements, ordered by tree and row
+    let mut searched: Vec<String> = talent_db.iter()
+        .filter(|(key, info)| ! info.filler
+                && talents.get_points(key).is_some())
+        .map(|(key, _)| key.to_string()).collect();
+    searched.sort_by_key(|key| (get_tree_index(&talent_db[key].tree),
+                                talent_db[key].row, key.to_string()));
+    return searched;
+}
+
+pub fn get_filler_points(tree: &str, searched: &[String], points: &[i32],
+                     talent_db: &HashMap<String,TalentInfo>)
+    -> Result<i32, String> {
+    // the fewest filler points that make the points of a tree legal
+    let n_rows = talent_db.values().map(|info| info.row).max().unwrap_or(0)
+        as usize + 1;
+    let mut row_points = vec![0; n_rows];
+    let mut forced: Vec<String> = Vec::new();
+    for (i, key) in searched.iter().enumerate() {
+        let info = &talent_db[key];
+        if info.tree != tree || points[i] == 0 { continue; }
+        if points[i] > info.max_points {
+            return Err(format!("{} has {} points, at most {} are allowed",
+                               info.name, points[i], info.max_points));
+        }
+        row_points[info.row as usize] += points[i];
+        if info.requires.is_empty() { continue; }
+
+        let required = talent_db.get(&info.requires)
+            .unwrap_or_else(|| panic!("{} requires the unknown talent {}",
+                                      info.name, info.requires));
+        match searched.iter().position(|other| *other == info.requires) {
+            Some(j) => if points[j] < required.max_points {
+                return Err(format!("{} requires all {} points in {}",
+                                   info.name, required.max_points,
+                                   required.name));
+            },
+            None => if ! forced.contains(&info.requires) {
+                forced.push(info.requires.to_string());
+            }
+        }
+    }
+
+    // fillers that are required are full, the rest are spent as needed
+    let mut filler = 0;
+    let mut capacity = vec![0; n_rows];
+    for (key, info) in talent_db {
+        if info.tree != tree || searched.contains(key) { continue; }
+        if forced.contains(key) {
+            row_points[info.row as usize] += info.max_points;
+            filler += info.max_points;
+        } else {
+            capacity[info.row as usize] += info.max_points;
+        }
+    }
+    let top_row = (0..n_rows).rev().find(|row| row_points[*row] > 0)
+        .unwrap_or(0);
+    for row in 2..top_row + 1 {
+        let below: i32 = row_points[..row].iter().sum();
+        let mut missing = POINTS_PER_ROW * (row as i32 - 1) - below;
+        // lower rows first, so the filler points unlock each other
+        for lower in 1..row {
+            let added = utils::min_i32(missing, capacity[lower]);
+            if added <= 0 { continue; }
+            row_points[lower] += added;
+            capacity[lower] -= added;
+            filler += added;
+            missing -= added;
+        }
+        if missing > 0 {
+            return Err(format!("row {} of {} needs {} points in the rows \
+                               below it", row, tree,
+                               POINTS_PER_ROW * (row as i32 - 1)));
+        }
+    }
+    return Ok(filler);
+}
+
 impl Talents {
+    pub fn get_points(&self, name: &str) -> Option<i32> {
+        // None for talents that the simulator does not implement
+        match name {
+            "improved_eviscerate" => return Some(self.improved_eviscerate),
+            "malice" => return Some(self.malice),
+            "ruthlessness" => return Some(self.ruthlessness),
+            "improved_slice_and_dice" =>
+                return Some(self.improved_slice_and_dice),
+            "relentless_strikes" => return Some(self.relentless_strikes),
+            "lethality" => return Some(self.lethality),
+            "improved_sinister_strike" =>
+                return Some(self.improved_sinister_strike),
+            "improved_backstab" => return Some(self.improved_backstab),
+            "precision" => return Some(self.precision),
+            "dagger_specialization" => return Some(self.dagger_specialization),
+            "dual_wield_specialization" =>
+                return Some(self.dual_wield_specialization),
+            "sword_specialization" => return Some(self.sword_specialization),
+            "weapon_expertise" => return Some(self.weapon_expertise),
+            "aggression" => return Some(self.aggression),
+            "opportunity" => return Some(self.opportunity),
+            _ => return None
+        }
+    }
+
+    pub fn set_points(&mut self, name: &str, points: i32) {
+        match name {
+            "improved_eviscerate" => self.improved_eviscerate = points,
+            "malice" => self.malice = points,
+            "ruthlessness" => self.ruthlessness = points,
+            "improved_slice_and_dice" => self.improved_slice_and_dice = points,
+            "relentless_strikes" => self.relentless_strikes = points,
+            "lethality" => self.lethality = points,
+            "improved_sinister_strike" => self.improved_sinister_strike = points,
+            "improved_backstab" => self.improved_backstab = points,
+            "precision" => self.precision = points,
+            "dagger_specialization" => self.dagger_specialization = points,
+            "dual_wield_specialization" =>
+                self.dual_wield_specialization = points,
+            "sword_specialization" => self.sword_specialization = points,
+            "weapon_expertise" => self.weapon_expertise = points,
+            "aggression" => self.aggression = points,
+            "opportunity" => self.opportunity = points,
+            _ => panic!("Talent {} is not implemented", name)
+        }
+    }
+
     fn new() -> Talents {
         Talents {
             // assassination
@@ -392,7 +642,7 @@ impl Talents {
     }
 }
 
-#[derive(Debug,Clone,Serialize,Deserialize)]
+#[derive(Debug,Clone,Copy,PartialEq,Serialize,Deserialize)]
 pub enum EnchantType {
     Permanent,
     Temporary,
@@ -414,7 +664,7 @@ pub struct SetBonus {
 }
 
 impl SetBonus {
-    fn copy(&self) -> SetBonus {
+    pub fn copy(&self) -> SetBonus {
         SetBonus {
             set_tag: self.set_tag.to_string(),
             pieces_needed: self.pieces_needed,
@@ -432,14 +682,24 @@ pub enum CooldownEffect {
     InstantEnergyRefill(i32) // energy
 }
 
+impl CooldownEffect {
+    pub fn has_duration(&self) -> bool {
+        match self {
+            CooldownEffect::InstantEnergyRefill(_) => return false,
+            _ => return true
+        }
+    }
+}
+
 #[derive(Clone,Debug,Serialize,Deserialize)]
 pub struct Cooldown {
     pub name: String,
     pub effect: CooldownEffect,
     pub is_active: bool,
-    pub time_left: f32,
+    pub active_since: f32,
+    pub active_until: f32,
     pub cd: f32,
-    pub cd_left: f32,
+    pub ready_at: f32,
     pub cost: i32,
     pub use_below_energy: i32
 }
@@ -452,9 +712,10 @@ impl Cooldown {
                 name: "Adrenaline rush".to_string(),
                 effect: CooldownEffect::EnergyRegenMultiplier(2, 15.0),
                 is_active: false,
-                time_left: 0.0,
+                active_since: 0.0,
+                active_until: 0.0,
                 cd: 5.0 * 60.0,
-                cd_left: 0.0,
+                ready_at: 0.0,
                 cost: 0,
                 use_below_energy: 50
             });
@@ -463,9 +724,10 @@ impl Cooldown {
                 name: "Blade flurry".to_string(),
                 effect: CooldownEffect::AttackSpeedMultiplier(1.2, 15.0),
                 is_active: false,
-                time_left: 0.0,
+                active_since: 0.0,
+                active_until: 0.0,
                 cd: 2.0 * 60.0,
-                cd_left: 0.0,
+                ready_at: 0.0,
                 cost: 20,
                 use_below_energy: 100
             });
@@ -474,9 +736,10 @@ impl Cooldown {
                 name: "Thistle tea".to_string(),
                 effect: CooldownEffect::InstantEnergyRefill(100),
                 is_active: false,
-                time_left: 0.0,
+                active_since: 0.0,
+                active_until: 0.0,
                 cd: 5.0 * 60.0,
-                cd_left: 0.0,
+                ready_at: 0.0,
                 cost: 0,
                 use_below_energy: 10
             });
@@ -498,19 +761,26 @@ pub struct Character {
     pub armor: Vec<Armor>,
     pub set_bonuses: Vec<SetBonus>,
     pub talents: Talents,
-    pub cooldowns: Vec<Cooldown>
+    pub cooldowns: Vec<Cooldown>,
+    pub target: Target
 }
 
 impl Character {
     pub fn create_character(args: &Args) -> Character {
+        let char_spec = CharacterSpecification::get_char_spec(args);
+        return Character::create_character_from_spec(char_spec, args);
+    }
+
+    pub fn create_character_from_spec(char_spec: CharacterSpecification,
+                                      args: &Args) -> Character {
         let mut character = Character::new(Race::Human);
 
-        let char_spec = CharacterSpecification::get_char_spec(args);
         character.set_armor_and_weapons(char_spec.items);
         character.apply_set_bonuses();
         character.set_enchants(char_spec.enchants);
         character.set_buffs(char_spec.buffs);
         character.set_talents(char_spec.talents);
+        character.set_target(char_spec.target, args);
         character.apply_stats_from_armor_and_weapons();
         character.apply_stats_from_enchants();
         character.apply_stats_from_talents();
@@ -553,7 +823,8 @@ impl Character {
             armor: Vec::new(),
             set_bonuses: Vec::new(),
             talents: Talents::new(),
-            cooldowns: Vec::new()
+            cooldowns: Vec::new(),
+            target: Target::default()
         }
     }
 
@@ -565,7 +836,7 @@ impl Character {
         self.sec_stats.crit += 0.01 * self.prim_stats.agility as f32 / 29.0;
     }
 
-    fn initialize_enchant_collection(&self) -> HashMap<String,Enchant> {
+    pub fn initialize_enchant_collection() -> HashMap<String,Enchant> {
 
         let enchant_string = fs::read_to_string(ENCHANT_COLLECTION_PATH)
                 .expect("Something went wrong reading enchants from file.");
@@ -576,7 +847,7 @@ impl Character {
     }
 
     fn set_enchants(&mut self, enchant_spec: EnchantSpecification) {
-        let enchant_collection = self.initialize_enchant_collection();
+        let enchant_collection = Character::initialize_enchant_collection();
 
         // armor enchants
         for enchant_name in &enchant_spec.armor_enchant_names {
@@ -611,6 +882,12 @@ impl Character {
         self.talents = talents;
     }
 
+    fn set_target(&mut self, target: Target, args: &Args) {
+        self.target = target;
+        // enemy level from the command line overrides the spec file
+        if args.enemy_lvl > 0 { self.target.level = args.enemy_lvl; }
+    }
+
     fn set_buffs(&mut self, buffs: Buffs) {
         self.buffs = buffs;
     }
@@ -667,13 +944,11 @@ impl Character {
         }
         let mh = self.get_weapon_by_name(item_spec.mh_name.to_string());
         self.mh = mh.copy();
-        self.mh.set_mean_dmg();
         let oh = self.get_weapon_by_name(item_spec.oh_name.to_string());
         self.oh = oh.copy();
-        self.oh.set_mean_dmg();
     }
 
-    fn get_set_bonus_db(&self) -> Vec<SetBonus> {
+    pub fn get_set_bonus_db(&self) -> Vec<SetBonus> {
         let set_bonuses_string = fs::read_to_string(SET_BONUSES_COLLECTION_PATH)
                 .expect("Something went wrong reading items from file.");
         let set_bonuses: Vec<SetBonus> = serde_yaml::from_str(
@@ -711,12 +986,15 @@ impl Character {
     fn apply_stats_from_talents(&mut self) {
         self.sec_stats.crit += 0.01 * self.talents.malice as f32;
         self.sec_stats.hit += 0.01 * self.talents.precision as f32;
-        self.prim_stats.dagger_skill += match self.talents.weapon_expertise {
-            0 => 0,
+        // talent points are validated with the specs file, weapon expertise
+        // covers both daggers and swords
+        let weapon_expertise_skill = match self.talents.weapon_expertise {
             1 => 3,
             2 => 5,
-            _ => panic!("Illegal value of weapon expertise")
+            _ => 0
         };
+        self.prim_stats.dagger_skill += weapon_expertise_skill;
+        self.prim_stats.sword_skill += weapon_expertise_skill;
     }
 
     fn apply_stats_from_enchants(&mut self) {
@@ -760,3 +1038,76 @@ impl Character {
         self.sec_stats.attack_power += sec_stats.attack_power;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_talent(max_points: i32, row: i32, requires: &str, filler: bool)
+        -> TalentInfo {
+        TalentInfo {
+            name: "".to_string(),
+            max_points,
+            tree: "Combat".to_string(),
+            row,
+            requires: requires.to_string(),
+            filler
+        }
+    }
+
+    fn get_test_talents() -> (HashMap<String,TalentInfo>, Vec<String>) {
+        // a small combat tree, searched talents first
+        let mut talent_db = HashMap::new();
+        talent_db.insert("a".to_string(), get_talent(5, 1, "", false));
+        talent_db.insert("b".to_string(), get_talent(5, 2, "", false));
+        talent_db.insert("needs_a".to_string(), get_talent(2, 3, "a", false));
+        talent_db.insert("needs_f".to_string(),
+                         get_talent(3, 4, "f_row_3", false));
+        talent_db.insert("f_row_1".to_string(), get_talent(5, 1, "", true));
+        talent_db.insert("f_row_3".to_string(), get_talent(1, 3, "", true));
+        let searched = vec!["a".to_string(), "b".to_string(),
+                            "needs_a".to_string(), "needs_f".to_string()];
+        return (talent_db, searched);
+    }
+
+    fn get_fillers(points: Vec<i32>) -> Result<i32, String> {
+        let (talent_db, searched) = get_test_talents();
+        return get_filler_points("Combat", &searched, &points, &talent_db);
+    }
+
+    #[test]
+    fn empty_tree_needs_no_filler() {
+        assert_eq!(get_fillers(vec![0, 0, 0, 0]), Ok(0));
+    }
+
+    #[test]
+    fn rows_are_unlocked_with_filler_points() {
+        assert_eq!(get_fillers(vec![0, 5, 0, 0]), Ok(5));
+        assert_eq!(get_fillers(vec![3, 5, 0, 0]), Ok(2));
+        assert_eq!(get_fillers(vec![5, 5, 0, 0]), Ok(0));
+    }
+
+    #[test]
+    fn required_talent_needs_all_points() {
+        assert!(get_fillers(vec![5, 5, 1, 0]).is_ok());
+        let error = get_fillers(vec![4, 5, 1, 0]).unwrap_err();
+        assert!(error.contains("requires"));
+    }
+
+    #[test]
+    fn required_filler_is_filled() {
+        // row 4 needs 15 points below, 10 searched and 1 required filler
+        assert_eq!(get_fillers(vec![5, 5, 0, 1]), Ok(5));
+    }
+
+    #[test]
+    fn rows_without_enough_filler_are_illegal() {
+        let error = get_fillers(vec![0, 5, 0, 1]).unwrap_err();
+        assert!(error.contains("row 4"));
+    }
+
+    #[test]
+    fn points_above_max_are_illegal() {
+        assert!(get_fillers(vec![6, 0, 0, 0]).is_err());
+    }
+}
@@ -0,0 +1,298 @@
+/* Combat log of a simulation.
+ *
+ * The simulator emits every event of a fight as a CombatEvent, which is
+ * handed to a CombatLogger. The loggers below print the familiar text log,
+ * write JSON Lines for post processing, or filter the events by category
+ * before passing them on to another logger.
+ */
+use serde::Serialize;
+
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{BufWriter,Write};
+
+use crate::output::OutputFormat;
+use crate::simulator::Hit;
+use crate::utils::Args;
+
+// every category of CombatEvent::get_category
+pub const LOG_CATEGORIES: [&str; 7] = ["swing", "ability", "procc", "energy",
+    "combo_points", "buff", "cooldown"];
+
+#[derive(Clone,Copy,Debug,Serialize)]
+pub enum ProccEffect {
+    Damage,
+    Strength,
+    ExtraAttack
+}
+
+#[derive(Debug,Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CombatEvent<'a> {
+    // white hits, extra attack holds the name of the procc that gave it
+    Swing { hand: &'a str, extra_attack: Option<&'a str>, hit: Hit, dmg: f32 },
+    Ability { name: &'a str, hit: Hit, dmg: f32 },
+    Procc { name: &'a str, effect: ProccEffect, dmg: f32 },
+    EnergyChanged { change: i32, energy: i32, source: &'a str },
+    ComboPointsChanged { combo_points: i32, source: &'a str },
+    BuffGained { name: &'a str, duration: f32 },
+    BuffFaded { name: &'a str },
+    CooldownUsed { name: &'a str },
+    CooldownFaded { name: &'a str, cooldown: f32 },
+    SwingTimerReset { hand: &'a str, swing_time: f32 }
+}
+
+impl<'a> CombatEvent<'a> {
+    pub fn get_category(&self) -> &'static str {
+        // the names accepted by the log filter
+        match self {
+            CombatEvent::Swing {..} => return "swing",
+            CombatEvent::SwingTimerReset {..} => return "swing",
+            CombatEvent::Ability {..} => return "ability",
+            CombatEvent::Procc {..} => return "procc",
+            CombatEvent::EnergyChanged {..} => return "energy",
+            CombatEvent::ComboPointsChanged {..} => return "combo_points",
+            CombatEvent::BuffGained {..} => return "buff",
+            CombatEvent::BuffFaded {..} => return "buff",
+            CombatEvent::CooldownUsed {..} => return "cooldown",
+            CombatEvent::CooldownFaded {..} => return "cooldown"
+        }
+    }
+
+    fn get_verbosity(&self) -> i32 {
+        // verbosity needed to show the event in the text log
+        match self {
+            CombatEvent::EnergyChanged { source, .. } => {
+                let is_routine = *source == ENERGY_TICKS
+                    || *source == ENERGY_SPENT;
+                if is_routine { return 2; }
+                else { return 1; }
+            },
+            CombatEvent::ComboPointsChanged { source, .. } => {
+                if *source == RUTHLESSNESS { return 1; }
+                else { return 2; }
+            },
+            CombatEvent::BuffFaded {..} => return 2,
+            CombatEvent::CooldownFaded {..} => return 2,
+            CombatEvent::SwingTimerReset {..} => return 2,
+            _ => return 1
+        }
+    }
+
+    fn get_text(&self) -> String {
+        match self {
+            CombatEvent::Swing { hand, extra_attack: Some(name), hit, dmg } =>
+                format!("{} extra attack ({}) {} for {:.0} dmg.", hand, name,
+                        hit, dmg),
+            CombatEvent::Swing { hand, extra_attack: None, hit, dmg } =>
+                format!("{} {} for {:.0} dmg.", hand, hit, dmg),
+            CombatEvent::Ability { name, hit, dmg } =>
+                format!("{} {} for {:.0} dmg.", name, hit, dmg),
+            CombatEvent::Procc { name, effect, dmg } => match effect {
+                ProccEffect::Damage =>
+                    format!("{} procc for {:.0} dmg!", name, dmg),
+                ProccEffect::Strength =>
+                    format!("Strength procc from {}!", name),
+                ProccEffect::ExtraAttack =>
+                    format!("Extra swing procc from {}!", name)
+            },
+            CombatEvent::EnergyChanged { change, energy, source } => {
+                if *source == ENERGY_SPENT {
+                    format!("Energy down to {}.", energy)
+                } else if *source == ENERGY_TICKS {
+                    format!("Energy refilled to {}.", energy)
+                } else {
+                    format!("Got {} energy from {}, energy is {}.", change,
+                            source, energy)
+                }
+            },
+            CombatEvent::ComboPointsChanged { combo_points, source } =>
+                format!("{} combo points from {}.", combo_points, source),
+            CombatEvent::BuffGained { name, duration } =>
+                format!("{} applied for {:.1}s.", name, duration),
+            CombatEvent::BuffFaded { name } => format!("{} wore off.", name),
+            CombatEvent::CooldownUsed { name } => format!("Used {}!", name),
+            CombatEvent::CooldownFaded { name, cooldown } =>
+                format!("{} wore off, {}s cooldown.", name, cooldown),
+            CombatEvent::SwingTimerReset { hand, swing_time } =>
+                format!("Reset {} swing timer to {:.2}s.", hand, swing_time)
+        }
+    }
+}
+
+// sources of energy and combo point changes that the text log treats
+// differently from the rest
+pub const ENERGY_TICKS: &str = "Energy ticks";
+pub const ENERGY_SPENT: &str = "Spent";
+pub const RUTHLESSNESS: &str = "Ruthlessness";
+
+#[derive(Serialize)]
+pub struct LogEntry<'a> {
+    pub iteration: u64,
+    pub time: f32,
+    #[serde(skip)]
+    pub time_left: f32,
+    #[serde(flatten)]
+    pub event: &'a CombatEvent<'a>
+}
+
+pub trait CombatLogger {
+    fn log(&mut self, entry: &LogEntry);
+}
+
+pub struct TextLogger {
+    verb: i32,
+    out: Box<dyn Write>
+}
+
+impl TextLogger {
+    pub fn new(verb: i32, out: Box<dyn Write>) -> TextLogger {
+        TextLogger { verb, out }
+    }
+}
+
+impl CombatLogger for TextLogger {
+    fn log(&mut self, entry: &LogEntry) {
+        if entry.event.get_verbosity() > self.verb { return; }
+        writeln!(self.out, "{:.1}: {}", entry.time_left,
+                 entry.event.get_text())
+            .expect("Could not write to the combat log");
+    }
+}
+
+pub struct JsonLinesLogger {
+    out: Box<dyn Write>
+}
+
+impl JsonLinesLogger {
+    pub fn new(out: Box<dyn Write>) -> JsonLinesLogger {
+        JsonLinesLogger { out }
+    }
+}
+
+impl CombatLogger for JsonLinesLogger {
+    fn log(&mut self, entry: &LogEntry) {
+        let line = serde_json::to_string(entry)
+            .expect("Could not serialize a combat log entry");
+        writeln!(self.out, "{}", line)
+            .expect("Could not write to the combat log");
+    }
+}
+
+pub struct FilterLogger {
+    categories: Vec<String>,
+    inner: Box<dyn CombatLogger>
+}
+
+impl FilterLogger {
+    pub fn new(categories: Vec<String>, inner: Box<dyn CombatLogger>)
+        -> FilterLogger {
+        FilterLogger { categories, inner }
+    }
+}
+
+impl CombatLogger for FilterLogger {
+    fn log(&mut self, entry: &LogEntry) {
+        let category = entry.event.get_category();
+        if self.categories.iter().any(|c| c == category) {
+            self.inner.log(entry);
+        }
+    }
+}
+
+pub struct CombatLog {
+    logger: Option<Box<dyn CombatLogger>>,
+    iteration: u64,
+    fight_length: f32
+}
+
+impl fmt::Debug for CombatLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CombatLog {{ active: {}, iteration: {} }}",
+               self.logger.is_some(), self.iteration)
+    }
+}
+
+impl CombatLog {
+    pub fn new() -> CombatLog {
+        CombatLog {
+            logger: None,
+            iteration: 0,
+            fight_length: 0.0
+        }
+    }
+
+    pub fn set_logger(&mut self, logger: Option<Box<dyn CombatLogger>>,
+                      fight_length: f32) {
+        self.logger = logger;
+        self.fight_length = fight_length;
+    }
+
+    pub fn start_iteration(&mut self, iteration: u64) {
+        self.iteration = iteration;
+    }
+
+    pub fn log(&mut self, now: f32, event: CombatEvent) {
+        if let Some(logger) = &mut self.logger {
+            logger.log(&LogEntry {
+                iteration: self.iteration,
+                time: now,
+                time_left: self.fight_length - now,
+                event: &event
+            });
+        }
+    }
+}
+
+pub fn logging_is_enabled(args: &Args) -> bool {
+    // stat weights and the commands simulate far too many fights to log
+    return (args.verb > 0 || ! args.log_format.is_empty()) && ! args.weights
+        && args.command.is_empty();
+}
+
+pub fn create_logger(args: &Args) -> Option<Box<dyn CombatLogger>> {
+    if ! logging_is_enabled(args) { return None; }
+
+    let out: Box<dyn Write> = if ! args.log_file.is_empty() {
+        // the file is truncated once at start up, every simulator appends
+        let file = OpenOptions::new().append(true).create(true)
+            .open(&args.log_file)
+            .expect("Could not open the combat log file");
+        Box::new(BufWriter::new(file))
+    } else if args.output == OutputFormat::Json {
+        // stdout is left to the json report
+        Box::new(std::io::stderr())
+    } else {
+        Box::new(std::io::stdout())
+    };
+
+    let mut logger: Box<dyn CombatLogger> = if args.log_format == "jsonl" {
+        Box::new(JsonLinesLogger::new(out))
+    } else {
+        // the text log shows minor events from verbosity 2
+        let verb = if args.verb > 0 { args.verb } else { 1 };
+        Box::new(TextLogger::new(verb, out))
+    };
+
+    if ! args.log_filter.is_empty() {
+        logger = Box::new(FilterLogger::new(args.log_filter.clone(), logger));
+    }
+    return Some(logger);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_plain_simulations_log() {
+        let mut args = Args::default_args();
+        args.verb = 1;
+        assert!(logging_is_enabled(&args));
+        args.command = "compare".to_string();
+        assert!(! logging_is_enabled(&args));
+        args.command = "".to_string();
+        args.weights = true;
+        assert!(! logging_is_enabled(&args));
+    }
+}
@@ -1,64 +1,239 @@
 /* TODO
  * - things taken out that will be reimplemented
  *   - weapon enchants
- *   - crusader proccs
  *
- * - Dynamic time steps
  * - Display everything in terms of atp
  * - 20% chance to apply a poison, base stat.
  * - 20% chance for a boss to resist the poison
  * - 130 dmg with current best instant poison
  */
 mod armory;
+mod combat_log;
+mod optimizer;
+mod output;
+mod report;
 mod utils;
 mod simulator;
 mod stats;
+mod validation;
 mod weights;
 
 extern crate rand;
 extern crate clap;
 extern crate serde;
 extern crate serde_yaml;
+extern crate serde_json;
 
 #[macro_use]
 extern crate enum_display_derive;
 
 use armory::Character;
+use output::{ComparedSpecReport,CompareReport,OutputFormat,StatWeightReport};
 use simulator::Simulator;
 use stats::OverallStats;
 use weights::StatShift;
 use utils::Args;
 
+use std::thread;
+
+// iterations added per round when running until a target precision
+const PRECISION_BATCH: i32 = 1_000;
+const MAX_PRECISION_ITERATIONS: i32 = 1_000_000;
+
+
+fn run_iterations(args: &Args, character: &Character, first: i32, 
+                  count: i32) -> OverallStats {
+    // shards the iterations over worker threads, each with its own simulator
+
+    let n_threads = utils::max_i32(1, utils::min_i32(args.threads, count));
+    let mut stats = OverallStats::new_from_args(args);
+
+    thread::scope(|scope| {
+        let mut handles = Vec::new();
+        let mut first_iteration = first;
+        for i in 0..n_threads {
+            let mut n_iterations = count / n_threads;
+            if i < count % n_threads { n_iterations += 1; }
+            let iterations = first_iteration..first_iteration + n_iterations;
+            first_iteration += n_iterations;
+
+            handles.push(scope.spawn(move || {
+                let mut simulator: Simulator = Simulator::new();
+                simulator.apply_input_arguments(args);
+                simulator.configure_with_character(character);
+
+                let mut thread_stats = OverallStats::new_from_args(args);
+                for iter in iterations {
+                    simulator.simulate(iter as u64);
+                    simulator.print_stats();
+                    thread_stats.import_current_data(simulator.get_stats());
+                }
+                return thread_stats;
+            }));
+        }
+        for handle in handles {
+            stats.merge(handle.join().expect("Simulation thread panicked"));
+        }
+    });
+    return stats;
+}
+
+fn is_precise_enough(args: &Args, all_stats: &[OverallStats]) -> bool {
+    // the first entry is the default run, the rest are compared against it
+    if all_stats[0].get_dps_half_width() > args.precision { return false; }
+    for i in 1..all_stats.len() {
+        if all_stats[i].get_dps_diff_half_width(&all_stats[0]) 
+            > args.precision { return false; }
+    }
+    return true;
+}
+
+fn run_all_iterations(args: &Args, characters: &Vec<Character>) 
+    -> Vec<OverallStats> {
+    // every character replays the same seeded iterations, so runs can be
+    // compared iteration by iteration
+
+    let mut all_stats: Vec<OverallStats> = Vec::new();
+    if args.precision <= 0.0 {
+        for character in characters {
+            all_stats.push(run_iterations(args, character, 0, 
+                                          args.iterations));
+        }
+        return all_stats;
+    }
+
+    let mut n_done = 0;
+    for character in characters {
+        all_stats.push(run_iterations(args, character, 0, PRECISION_BATCH));
+    }
+    n_done += PRECISION_BATCH;
+
+    while ! is_precise_enough(args, &all_stats) 
+        && n_done < MAX_PRECISION_ITERATIONS {
+        for (i, character) in characters.iter().enumerate() {
+            all_stats[i].merge(run_iterations(args, character, n_done, 
+                                              PRECISION_BATCH));
+        }
+        n_done += PRECISION_BATCH;
+    }
+
+    // json reports hold the iteration count instead
+    if args.output != OutputFormat::Json {
+        if is_precise_enough(args, &all_stats) {
+            println!("Reached ±{} dps after {} iterations.", args.precision, 
+                     n_done);
+        } else {
+            println!("Stopped at {} iterations without reaching ±{} dps.", 
+                     n_done, args.precision);
+        }
+    }
+    return all_stats;
+}
+
+fn write_csv(args: &Args, stats: &OverallStats) {
+    if ! args.csv_file.is_empty() {
+        if let Err(error) = stats.write_csv(&args.csv_file) {
+            eprintln!("Could not write {}: {}", args.csv_file, error);
+        }
+    }
+    if ! args.timeline_csv_file.is_empty() {
+        if let Err(error) = stats.write_timeline_csv(&args.timeline_csv_file) {
+            eprintln!("Could not write {}: {}", args.timeline_csv_file, 
+                      error);
+        }
+    }
+}
+
+fn write_report(args: &Args, character: &Character, stats: &OverallStats,
+                stat_weights: Vec<StatWeightReport>) {
+    if args.report_file.is_empty() { return; }
+    if let Err(error) = report::write_html_report(&args.report_file, 
+                                                  character, stats, 
+                                                  stat_weights) {
+        eprintln!("Could not write {}: {}", args.report_file, error);
+    }
+}
 
 fn get_stat_weights(args: &Args) {
 
     let stat_shifts = StatShift::new(args);
-    let mut default_dps: f32 = 0.0;
-    for (i, stat_shift) in stat_shifts.iter().enumerate() {
-
+    let mut characters: Vec<Character> = Vec::new();
+    for stat_shift in stat_shifts.iter() {
         let mut character = Character::create_character(args);
         character.apply_stat_shift(&stat_shift);
         character.convert_stats_and_set_cooldowns();
+        characters.push(character);
+    }
 
-        let mut simulator: Simulator = Simulator::new();
-        simulator.apply_input_arguments(args);
-        simulator.configure_with_character(&character);
+    let mut all_stats = run_all_iterations(args, &characters);
+    for (i, stat_shift) in stat_shifts.iter().enumerate() {
+        all_stats[i].add_weights_text(&stat_shift.text);
+    }
+    write_csv(args, &all_stats[0]);
 
-        let mut stats = OverallStats::new_from_args(args);
-        stats.add_weights_text(&stat_shift.text);
+    let mut stat_weights = Vec::new();
+    for i in 1..all_stats.len() {
+        stat_weights.push(all_stats[i].get_stat_weight_report(&all_stats[0]));
+    }
+    if args.output == OutputFormat::Json {
+        let mut report = all_stats[0].get_report();
+        report.stat_weights = stat_weights;
+        output::print_json(&report);
+        return;
+    }
+    write_report(args, &characters[0], &all_stats[0], stat_weights);
 
-        for _iter in 0..args.iterations {
-            simulator.simulate();
-            simulator.print_stats();
-            stats.import_current_data(simulator.get_stats());
-        }
-        if i == 0 { 
-            stats.print_stat_weight_default_run(); 
-            default_dps = stats.get_mean_dps();
-        }
-        else { 
-            stats.print_stat_weight_minus_default_dps(default_dps); 
-        }
+    all_stats[0].print_stat_weight_default_run(); 
+    for i in 1..all_stats.len() {
+        all_stats[i].print_stat_weight_minus_default_dps(&all_stats[0]); 
+    }
+}
+
+fn compare_specs(args: &Args) {
+    // every specs file replays the same seeded iterations, so the dps
+    // differences are paired per iteration
+    let mut characters: Vec<Character> = Vec::new();
+    for spec_file in &args.compare_files {
+        let mut spec_args = args.clone();
+        spec_args.spec_file = spec_file.to_string();
+        let mut character = Character::create_character(&spec_args);
+        character.convert_stats_and_set_cooldowns();
+        characters.push(character);
+    }
+
+    let all_stats = run_all_iterations(args, &characters);
+    let report = OverallStats::get_compare_report(&all_stats, 
+                                                  &args.compare_files);
+    if args.output == OutputFormat::Json {
+        output::print_json(&report);
+        return;
+    }
+    print_compare_report(&report);
+}
+
+fn get_compare_cells(spec: &ComparedSpecReport, is_baseline: bool)
+    -> (String, String, &'static str) {
+    // dps, delta and significance columns of a spec compared to the first
+    let dps = format!("{:>9.2} ±{:>5.2}", spec.dps.mean, spec.dps.ci95);
+    if is_baseline { return (dps, "baseline".to_string(), ""); }
+    let delta = format!("{:+.2} ±{:.2} ({:+.2}%)", spec.dps_diff.mean,
+                        spec.dps_diff.ci95, 100.0 * spec.dps_diff_ratio.mean);
+    let significant = if spec.significant { "yes" } else { "no" };
+    return (dps, delta, significant);
+}
+
+fn print_compare_report(report: &CompareReport) {
+    let name_width = report.specs.iter().map(|spec| spec.spec_file.len())
+        .max().unwrap_or(0).max(9);
+    let widths = [name_width, 17, 26];
+    println!("\nComparison over {} iterations of {}s each, seed {}.", 
+             report.iterations, report.fight_length, report.seed);
+    utils::print_table_row(&widths, &["Specs file", "Dps", "Delta vs first",
+                                      "Significant"]);
+    for (i, spec) in report.specs.iter().enumerate() {
+        let (dps, delta, significant) = get_compare_cells(spec, i == 0);
+        utils::print_table_row(&widths, &[&spec.spec_file, &dps, &delta,
+                                          significant]);
     }
 }
 
@@ -67,26 +242,33 @@ fn normal_simulation(args: &Args) {
     let mut character = Character::create_character(args);
     character.convert_stats_and_set_cooldowns();
 
-    let mut simulator: Simulator = Simulator::new();
-    simulator.apply_input_arguments(args);
-    simulator.configure_with_character(&character);
-
-    let mut stats = OverallStats::new_from_args(args);
-
-    for _iter in 0..args.iterations {
-        simulator.simulate();
-        simulator.print_stats();
-        stats.import_current_data(simulator.get_stats());
+    let characters = vec![character];
+    let all_stats = run_all_iterations(args, &characters);
+    write_csv(args, &all_stats[0]);
+    write_report(args, &characters[0], &all_stats[0], Vec::new());
+    if args.output == OutputFormat::Json {
+        output::print_json(&all_stats[0].get_report());
+        return;
     }
-    stats.print();
+    all_stats[0].print();
 
-    character.print_all_stats(args);
+    characters[0].print_all_stats(args);
 }
 
 fn main() {
 
     let args = utils::get_arguments();
-    if args.weights { get_stat_weights(&args); }
+    if ! args.log_file.is_empty() {
+        // every simulator appends to the log, so start from an empty file
+        std::fs::File::create(&args.log_file)
+            .expect("Could not create the combat log file");
+    }
+    if args.command == "compare" { compare_specs(&args); }
+    else if args.command == "optimize" { optimizer::optimize_gear(&args); }
+    else if args.command == "upgrades" { optimizer::find_upgrades(&args); }
+    else if args.command == "enchants" { optimizer::optimize_enchants(&args); }
+    else if args.command == "talents" { optimizer::optimize_talents(&args); }
+    else if args.weights { get_stat_weights(&args); }
     else { normal_simulation(&args); }
 
 }
@@ -0,0 +1,1238 @@
+/* Gear optimizer, run with the `optimize` command.
+ *
+ * Searching every combination of the item database with full simulations
+ * is far too slow, so the search is done in two steps. First every item is
+ * scored with stat weights, and weapon pairs, hit proccs and set bonuses
+ * with short simulations, all measured against the character of the specs
+ * file. The best estimated gear sets are then simulated in full, with
+ * shared random numbers, and the winner is written as a new specs file.
+ */
+use crate::armory::{Character,CharacterSpecification,Enchant,EnchantType,
+EnchantSpecification,HitProcc,ItemCollection,ItemSpecification,PrimStats,Race,
+SecStats,Slot,TalentInfo,TALENT_POINTS,TALENT_TREES,get_filler_points,
+get_searched_talents,get_tree_index};
+use crate::armory;
+use crate::output::CompareReport;
+use crate::stats::OverallStats;
+use crate::utils;
+use crate::utils::Args;
+use crate::weights::StatShift;
+
+use std::collections::HashMap;
+
+// iterations of the short simulations behind the estimates
+const ESTIMATE_ITERATIONS: i32 = 2_000;
+// best scored options per slot kept in the combination search, pieces of
+// sets with a bonus are always kept
+const SLOT_CANDIDATES: usize = 3;
+// armor slots and how many items each of them holds
+const ARMOR_SLOTS: [(Slot, usize); 13] = [(Slot::Head, 1), (Slot::Neck, 1),
+    (Slot::Shoulders, 1), (Slot::Back, 1), (Slot::Chest, 1),
+    (Slot::Wrists, 1), (Slot::Hands, 1), (Slot::Waist, 1), (Slot::Legs, 1),
+    (Slot::Feet, 1), (Slot::Ring, 2), (Slot::Trinket, 2), (Slot::Ranged, 1)];
+
+
+struct StatWeights {
+    // dps per point, crit, hit and haste per 1.0
+    agility: f32,
+    strength: f32,
+    crit: f32,
+    hit: f32,
+    haste: f32,
+    dagger_skill: f32,
+    sword_skill: f32
+}
+
+impl StatWeights {
+    fn get_score(&self, prim_stats: &PrimStats, sec_stats: &SecStats) -> f32 {
+        // a point of strength is a point of attack power
+        return self.agility * prim_stats.agility as f32
+            + self.strength * prim_stats.strength as f32
+            + self.strength * sec_stats.attack_power as f32
+            + self.crit * sec_stats.crit
+            + self.hit * sec_stats.hit
+            + self.haste * sec_stats.haste
+            + self.dagger_skill * prim_stats.dagger_skill as f32
+            + self.sword_skill * prim_stats.sword_skill as f32;
+    }
+}
+
+#[derive(Clone)]
+struct GearOption {
+    armor_names: Vec<String>,
+    set_tags: Vec<String>,
+    score: f32
+}
+
+struct Candidate {
+    items: ItemSpecification,
+    estimate: f32
+}
+
+pub fn get_quiet_args(args: &Args) -> Args {
+    // the searches simulate many characters, none of them worth a log
+    let mut quiet_args = args.clone();
+    quiet_args.verb = 0;
+    quiet_args.log_format = "".to_string();
+    quiet_args.weights = false;
+    return quiet_args;
+}
+
+pub fn get_estimate_dps_diffs(args: &Args, characters: &Vec<Character>)
+    -> Vec<f32> {
+    // short simulations, as dps differences against the first character
+    let mut estimate_args = get_quiet_args(args);
+    estimate_args.iterations = ESTIMATE_ITERATIONS;
+    estimate_args.precision = 0.0;
+    let all_stats = crate::run_all_iterations(&estimate_args, characters);
+    let base_dps = all_stats[0].get_mean_dps();
+    return all_stats.iter().map(|stats| stats.get_mean_dps() - base_dps)
+        .collect();
+}
+
+fn get_stat_shift(text: &str) -> StatShift {
+    StatShift {
+        text: text.to_string(),
+        prim_stats: PrimStats::new_from_race(Race::None),
+        sec_stats: SecStats::new_from_race(Race::None)
+    }
+}
+
+fn estimate_stat_weights(args: &Args) -> StatWeights {
+    let mut shifts = vec![get_stat_shift("none"), get_stat_shift("agility"),
+        get_stat_shift("strength"), get_stat_shift("crit"),
+        get_stat_shift("hit"), get_stat_shift("haste"),
+        get_stat_shift("dagger skill"), get_stat_shift("sword skill")];
+    shifts[1].prim_stats.agility = 20;
+    shifts[2].prim_stats.strength = 20;
+    shifts[3].sec_stats.crit = 0.01;
+    shifts[4].sec_stats.hit = 0.01;
+    shifts[5].sec_stats.haste = 0.01;
+    shifts[6].prim_stats.dagger_skill = 3;
+    shifts[7].prim_stats.sword_skill = 3;
+
+    let mut characters = Vec::new();
+    for shift in &shifts {
+        let mut character = Character::create_character(args);
+        character.apply_stat_shift(shift);
+        character.convert_stats_and_set_cooldowns();
+        characters.push(character);
+    }
+    let diffs = get_estimate_dps_diffs(args, &characters);
+    StatWeights {
+        agility: diffs[1] / 20.0,
+        strength: diffs[2] / 20.0,
+        crit: diffs[3] / 0.01,
+        hit: diffs[4] / 0.01,
+        haste: diffs[5] / 0.01,
+        dagger_skill: diffs[6] / 3.0,
+        sword_skill: diffs[7] / 3.0
+    }
+}
+
+fn estimate_set_bonuses(args: &Args) -> HashMap<String,(i32, f32)> {
+    // (pieces needed, dps) of every set bonus, measured by adding it to
+    // the character of the specs file
+    let base = Character::create_character(args);
+    let set_bonuses = base.get_set_bonus_db();
+    let mut characters = Vec::new();
+    for set_bonus in &set_bonuses {
+        let mut without = Character::create_character(args);
+        without.set_bonuses.retain(|bonus| bonus.set_tag != set_bonus.set_tag);
+        without.convert_stats_and_set_cooldowns();
+        let mut with = Character::create_character(args);
+        with.set_bonuses.retain(|bonus| bonus.set_tag != set_bonus.set_tag);
+        with.set_bonuses.push(set_bonus.copy());
+        with.convert_stats_and_set_cooldowns();
+        characters.push(without);
+        characters.push(with);
+    }
+
+    let mut values = HashMap::new();
+    if characters.is_empty() { return values; }
+    let diffs = get_estimate_dps_diffs(args, &characters);
+    for (i, set_bonus) in set_bonuses.iter().enumerate() {
+        values.insert(set_bonus.set_tag.to_string(),
+                      (set_bonus.pieces_needed,
+                       diffs[2 * i + 1] - diffs[2 * i]));
+    }
+    return values;
+}
+
+fn get_character_without_procc(args: &Args, armor_name: &str) -> Character {
+    let mut character = Character::create_character(args);
+    for worn in character.armor.iter_mut() {
+        if worn.get_name() == armor_name { worn.hit_procc = HitProcc::None; }
+    }
+    return character;
+}
+
+fn estimate_armor_proccs(args: &Args, collection: &ItemCollection)
+    -> HashMap<String,f32> {
+    // dps of the hit procc of every armor piece that has one, measured by
+    // moving the procc onto the gear of the specs file
+    let mut names: Vec<&String> = collection.armor.iter()
+        .filter(|(_, armor)| armor.hit_procc != HitProcc::None)
+        .map(|(name, _)| name).collect();
+    names.sort();
+
+    let mut characters = Vec::new();
+    for name in &names {
+        let armor = &collection.armor[*name];
+        let mut without = get_character_without_procc(args, armor.get_name());
+        let mut with = get_character_without_procc(args, armor.get_name());
+        if let Some(carrier) = with.armor.iter_mut()
+            .find(|worn| worn.hit_procc == HitProcc::None) {
+            carrier.hit_procc = armor.hit_procc.clone();
+        }
+        without.convert_stats_and_set_cooldowns();
+        with.convert_stats_and_set_cooldowns();
+        characters.push(without);
+        characters.push(with);
+    }
+
+    let mut values = HashMap::new();
+    if characters.is_empty() { return values; }
+    let diffs = get_estimate_dps_diffs(args, &characters);
+    for (i, name) in names.iter().enumerate() {
+        values.insert(name.to_string(), diffs[2 * i + 1] - diffs[2 * i]);
+    }
+    return values;
+}
+
+fn estimate_weapon_pairs(args: &Args, base_spec: &CharacterSpecification,
+                         collection: &ItemCollection)
+    -> Vec<(String, String, f32)> {
+    // (mh, oh, dps against the current weapons) of every legal pair
+    let mut names: Vec<&String> = collection.weapons.keys().collect();
+    names.sort();
+    let mut pairs = Vec::new();
+    for mh_name in &names {
+        for oh_name in &names {
+            let mh = &collection.weapons[*mh_name];
+            let oh = &collection.weapons[*oh_name];
+            if ! mh.fits_slot(Slot::MH) || ! oh.fits_slot(Slot::OH) { continue; }
+            if mh_name == oh_name && mh.is_unique() { continue; }
+            pairs.push((mh_name.to_string(), oh_name.to_string()));
+        }
+    }
+
+    let mut characters = vec![Character::create_character_from_spec(
+            base_spec.clone(), args)];
+    for (mh_name, oh_name) in &pairs {
+        let mut spec = base_spec.clone();
+        spec.items.mh_name = mh_name.to_string();
+        spec.items.oh_name = oh_name.to_string();
+        characters.push(Character::create_character_from_spec(spec, args));
+    }
+    for character in characters.iter_mut() {
+        character.convert_stats_and_set_cooldowns();
+    }
+    let diffs = get_estimate_dps_diffs(args, &characters);
+
+    let mut estimates = Vec::new();
+    for (i, (mh_name, oh_name)) in pairs.into_iter().enumerate() {
+        estimates.push((mh_name, oh_name, diffs[i + 1]));
+    }
+    estimates.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
+    return estimates;
+}
+
+fn get_armor_score(name: &str, collection: &ItemCollection,
+                   weights: &StatWeights, proccs: &HashMap<String,f32>) -> f32 {
+    let armor = &collection.armor[name];
+    return weights.get_score(&armor.prim_stats, &armor.sec_stats)
+        + proccs.get(name).unwrap_or(&0.0);
+}
+
+fn get_slot_options(slot: Slot, n_items: usize, collection: &ItemCollection,
+                    weights: &StatWeights, proccs: &HashMap<String,f32>,
+                    set_bonuses: &HashMap<String,(i32, f32)>)
+    -> Vec<GearOption> {
+    let mut names: Vec<&String> = collection.armor.iter()
+        .filter(|(_, armor)| armor.slot == slot)
+        .map(|(name, _)| name).collect();
+    names.sort();
+
+    // every choice of n different items, or all of them if there are fewer
+    let mut choices: Vec<Vec<&String>> = Vec::new();
+    if names.len() <= n_items {
+        if ! names.is_empty() { choices.push(names.clone()); }
+    } else if n_items == 1 {
+        for name in &names { choices.push(vec![*name]); }
+    } else {
+        for i in 0..names.len() {
+            for j in i + 1..names.len() {
+                choices.push(vec![names[i], names[j]]);
+            }
+        }
+    }
+
+    let mut options = Vec::new();
+    for choice in choices {
+        let mut option = GearOption {
+            armor_names: Vec::new(),
+            set_tags: Vec::new(),
+            score: 0.0
+        };
+        for name in choice {
+            let armor = &collection.armor[name];
+            option.armor_names.push(name.to_string());
+            if ! armor.set_tag.is_empty() {
+                option.set_tags.push(armor.set_tag.to_string());
+            }
+            option.score += get_armor_score(name, collection, weights,
+                                            proccs);
+        }
+        options.push(option);
+    }
+    options.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+
+    let mut kept = Vec::new();
+    for (i, option) in options.into_iter().enumerate() {
+        let has_set_piece = option.set_tags.iter()
+            .any(|tag| set_bonuses.contains_key(tag));
+        if i < SLOT_CANDIDATES || has_set_piece { kept.push(option); }
+    }
+    return kept;
+}
+
+fn add_set_bonus_score(option: &mut GearOption,
+                       set_bonuses: &HashMap<String,(i32, f32)>) {
+    let mut pieces: HashMap<&String,i32> = HashMap::new();
+    for tag in &option.set_tags { *pieces.entry(tag).or_insert(0) += 1; }
+    for (tag, (pieces_needed, value)) in set_bonuses {
+        if pieces.get(tag).unwrap_or(&0) >= pieces_needed {
+            option.score += value;
+        }
+    }
+}
+
+fn get_armor_combinations(slot_options: &Vec<Vec<GearOption>>)
+    -> Vec<GearOption> {
+    // every combination of one option per slot, scores are summed
+    let mut combinations = vec![GearOption {
+        armor_names: Vec::new(),
+        set_tags: Vec::new(),
+        score: 0.0
+    }];
+    for options in slot_options {
+        let mut extended = Vec::new();
+        for combination in &combinations {
+            for option in options {
+                let mut new_combination = combination.clone();
+                new_combination.armor_names.extend(
+                    option.armor_names.iter().cloned());
+                new_combination.set_tags.extend(
+                    option.set_tags.iter().cloned());
+                new_combination.score += option.score;
+                extended.push(new_combination);
+            }
+        }
+        combinations = extended;
+    }
+    return combinations;
+}
+
+fn is_same_gear(a: &ItemSpecification, b: &ItemSpecification) -> bool {
+    let mut a_names = a.armor_names.clone();
+    let mut b_names = b.armor_names.clone();
+    a_names.sort();
+    b_names.sort();
+    return a.mh_name == b.mh_name && a.oh_name == b.oh_name
+        && a_names == b_names;
+}
+
+fn get_candidates(args: &Args, base_spec: &CharacterSpecification,
+                  collection: &ItemCollection) -> (usize, Vec<Candidate>) {
+    let weights = estimate_stat_weights(args);
+    let proccs = estimate_armor_proccs(args, collection);
+    let set_bonuses = estimate_set_bonuses(args);
+    let weapon_pairs = estimate_weapon_pairs(args, base_spec, collection);
+
+    // estimates are given against the current armor
+    let mut current = GearOption {
+        armor_names: base_spec.items.armor_names.clone(),
+        set_tags: Vec::new(),
+        score: 0.0
+    };
+    for name in &base_spec.items.armor_names {
+        let armor = &collection.armor[name];
+        if ! armor.set_tag.is_empty() {
+            current.set_tags.push(armor.set_tag.to_string());
+        }
+        current.score += get_armor_score(name, collection, &weights, &proccs);
+    }
+    add_set_bonus_score(&mut current, &set_bonuses);
+
+    let mut slot_options = Vec::new();
+    for (slot, n_items) in ARMOR_SLOTS.iter() {
+        let options = get_slot_options(*slot, *n_items, collection, &weights,
+                                       &proccs, &set_bonuses);
+        if ! options.is_empty() { slot_options.push(options); }
+    }
+    let mut armor_sets = get_armor_combinations(&slot_options);
+    for armor_set in armor_sets.iter_mut() {
+        add_set_bonus_score(armor_set, &set_bonuses);
+    }
+    armor_sets.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+    let n_estimated = armor_sets.len() * weapon_pairs.len();
+
+    // the estimates add up, so the best candidates are among the best
+    // armor sets combined with the best weapon pairs
+    let n_top = args.top_candidates.max(1) as usize;
+    let mut candidates = Vec::new();
+    for armor_set in armor_sets.iter().take(n_top) {
+        for (mh_name, oh_name, weapon_score) in weapon_pairs.iter().take(n_top) {
+            let items = ItemSpecification {
+                mh_name: mh_name.to_string(),
+                oh_name: oh_name.to_string(),
+                armor_names: armor_set.armor_names.clone()
+            };
+            if is_same_gear(&items, &base_spec.items) { continue; }
+            candidates.push(Candidate {
+                items,
+                estimate: armor_set.score - current.score + weapon_score
+            });
+        }
+    }
+    candidates.sort_by(|a, b| b.estimate.partial_cmp(&a.estimate).unwrap());
+    candidates.truncate(n_top);
+    return (n_estimated, candidates);
+}
+
+fn get_item_display_name(name: &str, collection: &ItemCollection) -> String {
+    if let Some(armor) = collection.armor.get(name) {
+        return armor.get_name().to_string();
+    }
+    if let Some(weapon) = collection.weapons.get(name) {
+        return weapon.get_name().to_string();
+    }
+    return name.to_string();
+}
+
+fn print_gear_changes(current: &ItemSpecification, best: &ItemSpecification,
+                      collection: &ItemCollection) {
+    if current.mh_name != best.mh_name {
+        println!("MH: {} -> {}",
+                 get_item_display_name(&current.mh_name, collection),
+                 get_item_display_name(&best.mh_name, collection));
+    }
+    if current.oh_name != best.oh_name {
+        println!("OH: {} -> {}",
+                 get_item_display_name(&current.oh_name, collection),
+                 get_item_display_name(&best.oh_name, collection));
+    }
+    for name in &current.armor_names {
+        if ! best.armor_names.contains(name) {
+            println!("- {}", get_item_display_name(name, collection));
+        }
+    }
+    for name in &best.armor_names {
+        if ! current.armor_names.contains(name) {
+            println!("+ {}", get_item_display_name(name, collection));
+        }
+    }
+}
+
+fn print_optimizer_results(report: &CompareReport, estimates: &[f32],
+                           label: &str) {
+    let widths = [10, 10, 17, 26];
+    println!("
+Simulated over {} iterations of {}s each, seed {}.",
+             report.iterations, report.fight_length, report.seed);
+    utils::print_table_row(&widths, &[label, "Estimate", "Dps",
+                                      "Delta vs current", "Significant"]);
+    for (i, spec) in report.specs.iter().enumerate() {
+        let estimate = if i == 0 { "".to_string() }
+            else { format!("{:+.1}", estimates[i - 1]) };
+        let (dps, delta, significant) = crate::get_compare_cells(spec, i == 0);
+        utils::print_table_row(&widths, &[&spec.spec_file, &estimate, &dps,
+                                          &delta, significant]);
+    }
+}
+
+pub fn optimize_gear(args: &Args) {
+    let quiet_args = get_quiet_args(args);
+    let base_spec = CharacterSpecification::get_char_spec(args);
+    let collection = ItemCollection::initialize_item_collection();
+
+    let (n_estimated, candidates) = get_candidates(args, &base_spec,
+                                                   &collection);
+    println!("Estimated {} gear sets, simulating the best {} against the \
+             current gear.", n_estimated, candidates.len());
+
+    // the current gear goes first, every candidate is compared against it
+    let mut names = vec!["current".to_string()];
+    let mut characters = vec![Character::create_character_from_spec(
+            base_spec.clone(), args)];
+    for (i, candidate) in candidates.iter().enumerate() {
+        let mut spec = base_spec.clone();
+        spec.items = candidate.items.clone();
+        names.push(format!("#{}", i + 1));
+        characters.push(Character::create_character_from_spec(spec, args));
+    }
+    for character in characters.iter_mut() {
+        character.convert_stats_and_set_cooldowns();
+    }
+    let all_stats = crate::run_all_iterations(&quiet_args, &characters);
+    let report = OverallStats::get_compare_report(&all_stats, &names);
+    let estimates: Vec<f32> = candidates.iter().map(|c| c.estimate).collect();
+    print_optimizer_results(&report, &estimates, "Gear");
+
+    let mut best = 0;
+    for i in 1..report.specs.len() {
+        if report.specs[i].dps.mean > report.specs[best].dps.mean { best = i; }
+    }
+    let mut best_spec = base_spec.clone();
+    if best == 0 {
+        println!("\nNo candidate beat the current gear.");
+    } else {
+        best_spec.items = candidates[best - 1].items.clone();
+        println!("\nBest gear is {}, changes against the current gear:",
+                 names[best]);
+        print_gear_changes(&base_spec.items, &best_spec.items, &collection);
+    }
+
+    let header = format!("gear optimized from {}", args.spec_file);
+    match best_spec.write_to_file(&args.save_file, &header) {
+        Ok(()) => println!("Wrote the best spec to {}.", args.save_file),
+        Err(error) => eprintln!("Could not write {}: {}", args.save_file,
+                                error)
+    }
+}
+
+struct Upgrade {
+    slot: Slot,
+    name: String,
+    replaced: String,
+    items: ItemSpecification
+}
+
+fn get_set_bonus_changes(current: &Character, upgraded: &Character) -> String {
+    let mut changes = Vec::new();
+    for bonus in &upgraded.set_bonuses {
+        if ! current.set_bonuses.iter().any(|b| b.set_tag == bonus.set_tag) {
+            changes.push(format!("gains {} bonus", bonus.set_tag));
+        }
+    }
+    for bonus in &current.set_bonuses {
+        if ! upgraded.set_bonuses.iter().any(|b| b.set_tag == bonus.set_tag) {
+            changes.push(format!("breaks {} bonus", bonus.set_tag));
+        }
+    }
+    return changes.join(", ");
+}
+
+fn get_upgrades(base_spec: &CharacterSpecification,
+                collection: &ItemCollection) -> Vec<Upgrade> {
+    // every item of the database swapped in for a worn one, rings and
+    // trinkets replace either of the two worn
+    let items = &base_spec.items;
+    let mut upgrades = Vec::new();
+
+    let mut weapon_names: Vec<&String> = collection.weapons.keys().collect();
+    weapon_names.sort();
+    for name in &weapon_names {
+        let weapon = &collection.weapons[*name];
+        if weapon.fits_slot(Slot::MH) && **name != items.mh_name
+            && ! (weapon.is_unique() && **name == items.oh_name) {
+            let mut upgraded = items.clone();
+            upgraded.mh_name = name.to_string();
+            upgrades.push(Upgrade { slot: Slot::MH, name: name.to_string(),
+                replaced: items.mh_name.to_string(), items: upgraded });
+        }
+        if weapon.fits_slot(Slot::OH) && **name != items.oh_name
+            && ! (weapon.is_unique() && **name == items.mh_name) {
+            let mut upgraded = items.clone();
+            upgraded.oh_name = name.to_string();
+            upgrades.push(Upgrade { slot: Slot::OH, name: name.to_string(),
+                replaced: items.oh_name.to_string(), items: upgraded });
+        }
+    }
+
+    let mut armor_names: Vec<&String> = collection.armor.keys().collect();
+    armor_names.sort();
+    for name in armor_names {
+        if items.armor_names.contains(name) { continue; }
+        let slot = collection.armor[name].slot;
+        let worn: Vec<&String> = items.armor_names.iter()
+            .filter(|worn| collection.armor[*worn].slot == slot).collect();
+        if worn.is_empty() {
+            // nothing worn in the slot, the item is simply added
+            let mut upgraded = items.clone();
+            upgraded.armor_names.push(name.to_string());
+            upgrades.push(Upgrade { slot, name: name.to_string(),
+                replaced: "".to_string(), items: upgraded });
+        }
+        for worn_name in worn {
+            let mut upgraded = items.clone();
+            for armor_name in upgraded.armor_names.iter_mut() {
+                if armor_name == worn_name { *armor_name = name.to_string(); }
+            }
+            upgrades.push(Upgrade { slot, name: name.to_string(),
+                replaced: worn_name.to_string(), items: upgraded });
+        }
+    }
+    return upgrades;
+}
+
+pub fn find_upgrades(args: &Args) {
+    let quiet_args = get_quiet_args(args);
+    let base_spec = CharacterSpecification::get_char_spec(args);
+    let collection = ItemCollection::initialize_item_collection();
+    let upgrades = get_upgrades(&base_spec, &collection);
+
+    let mut names = vec!["current".to_string()];
+    let mut characters = vec![Character::create_character_from_spec(
+            base_spec.clone(), args)];
+    for upgrade in &upgrades {
+        let mut spec = base_spec.clone();
+        spec.items = upgrade.items.clone();
+        names.push(upgrade.name.to_string());
+        characters.push(Character::create_character_from_spec(spec, args));
+    }
+    let mut set_bonus_changes = Vec::new();
+    for character in &characters {
+        set_bonus_changes.push(get_set_bonus_changes(&characters[0],
+                                                     character));
+    }
+    for character in characters.iter_mut() {
+        character.convert_stats_and_set_cooldowns();
+    }
+    println!("Simulating {} item swaps against the current gear.",
+             upgrades.len());
+    let all_stats = crate::run_all_iterations(&quiet_args, &characters);
+    let report = OverallStats::get_compare_report(&all_stats, &names);
+    println!("\nCurrent gear: {:.2} ±{:.2} dps over {} iterations of {}s \
+             each, seed {}.", report.specs[0].dps.mean,
+             report.specs[0].dps.ci95, report.iterations,
+             report.fight_length, report.seed);
+    println!("Columns: dps gain ±95% interval, relative gain, significant, \
+             item.");
+
+    for (slot, _) in ARMOR_SLOTS.iter().chain([(Slot::MH, 1), (Slot::OH, 1)]
+                                                .iter()) {
+        // the best swap of every item in the slot, best first
+        let mut best: HashMap<&String,usize> = HashMap::new();
+        for (i, upgrade) in upgrades.iter().enumerate() {
+            if upgrade.slot != *slot { continue; }
+            let entry = best.entry(&upgrade.name).or_insert(i);
+            if report.specs[i + 1].dps.mean
+                > report.specs[*entry + 1].dps.mean { *entry = i; }
+        }
+        if best.is_empty() { continue; }
+        let mut ranked: Vec<usize> = best.values().cloned().collect();
+        ranked.sort_by(|a, b| report.specs[*b + 1].dps.mean
+                       .partial_cmp(&report.specs[*a + 1].dps.mean).unwrap());
+
+        println!("\n{:?}:", slot);
+        for i in ranked {
+            let upgrade = &upgrades[i];
+            let spec = &report.specs[i + 1];
+            let mut note = if upgrade.replaced.is_empty() {
+                "added".to_string()
+            } else {
+                format!("replaces {}", get_item_display_name(
+                        &upgrade.replaced, &collection))
+            };
+            if ! set_bonus_changes[i + 1].is_empty() {
+                note = format!("{}, {}", note, set_bonus_changes[i + 1]);
+            }
+            println!("{:>+9.2} ±{:<6.2}{:>+7.2}%  {:<3}  {:<30} {}",
+                     spec.dps_diff.mean, spec.dps_diff.ci95,
+                     100.0 * spec.dps_diff_ratio.mean,
+                     if spec.significant { "yes" } else { "no" },
+                     get_item_display_name(&upgrade.name, &collection), note);
+        }
+    }
+}
+
+struct EnchantPosition {
+    // an armor slot, or a weapon hand with a permanent or temporary enchant
+    slot: Slot,
+    enchant_type: EnchantType,
+    current: String, // empty if nothing is applied
+    options: Vec<String> // empty for nothing applied
+}
+
+struct EnchantLayout {
+    positions: Vec<EnchantPosition>,
+    // enchants of the specs file that have no position, kept as they are
+    armor_extra: Vec<String>,
+    mh_extra: Vec<String>,
+    oh_extra: Vec<String>
+}
+
+impl EnchantPosition {
+    fn get_label(&self) -> String {
+        if self.slot == Slot::MH || self.slot == Slot::OH {
+            let kind = match self.enchant_type {
+                EnchantType::Permanent => "permanent",
+                EnchantType::Temporary => "temporary"
+            };
+            return format!("{:?} {}", self.slot, kind);
+        }
+        return format!("{:?}", self.slot);
+    }
+}
+
+fn get_enchant_options(enchants: &HashMap<String,Enchant>, slot: Slot,
+                       enchant_type: Option<EnchantType>) -> Vec<String> {
+    let mut options: Vec<String> = enchants.iter()
+        .filter(|(_, enchant)| enchant.slot.contains(&slot))
+        .filter(|(_, enchant)| enchant_type.is_none()
+                || Some(enchant.enchant_type) == enchant_type)
+        .map(|(name, _)| name.to_string()).collect();
+    options.sort();
+    options.push("".to_string());
+    return options;
+}
+
+fn get_enchant_layout(base_spec: &CharacterSpecification,
+                      collection: &ItemCollection,
+                      enchants: &HashMap<String,Enchant>) -> EnchantLayout {
+    let mut layout = EnchantLayout {
+        positions: Vec::new(),
+        armor_extra: Vec::new(),
+        mh_extra: Vec::new(),
+        oh_extra: Vec::new()
+    };
+
+    // one position per worn armor slot that can be enchanted
+    for (slot, _) in ARMOR_SLOTS.iter() {
+        let worn = base_spec.items.armor_names.iter()
+            .any(|name| collection.armor[name].slot == *slot);
+        let options = get_enchant_options(enchants, *slot, None);
+        if worn && options.len() > 1 {
+            layout.positions.push(EnchantPosition {
+                slot: *slot,
+                enchant_type: EnchantType::Permanent,
+                current: "".to_string(),
+                options
+            });
+        }
+    }
+    // the specs file lists armor enchants without slots, so they take the
+    // first free slot they fit
+    for name in &base_spec.enchants.armor_enchant_names {
+        let position = layout.positions.iter_mut().find(|position|
+            position.current.is_empty() && position.options.contains(name));
+        match position {
+            Some(position) => position.current = name.to_string(),
+            None => layout.armor_extra.push(name.to_string())
+        }
+    }
+
+    for slot in &[Slot::MH, Slot::OH] {
+        let (names, extra) = if *slot == Slot::MH {
+            (&base_spec.enchants.mh_enchant_names, &mut layout.mh_extra)
+        } else {
+            (&base_spec.enchants.oh_enchant_names, &mut layout.oh_extra)
+        };
+        for enchant_type in &[EnchantType::Permanent, EnchantType::Temporary] {
+            let mut position = EnchantPosition {
+                slot: *slot,
+                enchant_type: *enchant_type,
+                current: "".to_string(),
+                options: get_enchant_options(enchants, *slot,
+                                             Some(*enchant_type))
+            };
+            for name in names {
+                if position.current.is_empty()
+                    && position.options.contains(name) {
+                    position.current = name.to_string();
+                }
+            }
+            layout.positions.push(position);
+        }
+        for name in names {
+            if ! layout.positions.iter().any(|position|
+                position.slot == *slot && position.current == *name) {
+                extra.push(name.to_string());
+            }
+        }
+    }
+    return layout;
+}
+
+fn get_enchant_spec(layout: &EnchantLayout, choices: &[String])
+    -> EnchantSpecification {
+    let mut spec = EnchantSpecification {
+        armor_enchant_names: layout.armor_extra.clone(),
+        mh_enchant_names: layout.mh_extra.clone(),
+        oh_enchant_names: layout.oh_extra.clone()
+    };
+    for (position, choice) in layout.positions.iter().zip(choices.iter()) {
+        if choice.is_empty() { continue; }
+        match position.slot {
+            Slot::MH => spec.mh_enchant_names.push(choice.to_string()),
+            Slot::OH => spec.oh_enchant_names.push(choice.to_string()),
+            _ => spec.armor_enchant_names.push(choice.to_string())
+        }
+    }
+    return spec;
+}
+
+fn get_enchant_display_name(name: &str, enchants: &HashMap<String,Enchant>)
+    -> String {
+    if name.is_empty() { return "(nothing)".to_string(); }
+    return match enchants.get(name) {
+        Some(enchant) => enchant.name.to_string(),
+        None => name.to_string()
+    };
+}
+
+pub fn optimize_enchants(args: &Args) {
+    let quiet_args = get_quiet_args(args);
+    let base_spec = CharacterSpecification::get_char_spec(args);
+    let collection = ItemCollection::initialize_item_collection();
+    let base = Character::create_character_from_spec(base_spec.clone(), args);
+    let enchants = Character::initialize_enchant_collection();
+    let layout = get_enchant_layout(&base_spec, &collection, &enchants);
+    let current: Vec<String> = layout.positions.iter()
+        .map(|position| position.current.to_string()).collect();
+
+    // every alternative is tried with the rest of the enchants unchanged
+    let mut alternatives: Vec<(usize, String)> = Vec::new();
+    let mut names = vec!["current".to_string()];
+    let mut characters = vec![base];
+    for (i, position) in layout.positions.iter().enumerate() {
+        for option in &position.options {
+            if *option == position.current { continue; }
+            let mut choices = current.clone();
+            choices[i] = option.to_string();
+            let mut spec = base_spec.clone();
+            spec.enchants = get_enchant_spec(&layout, &choices);
+            alternatives.push((i, option.to_string()));
+            names.push(option.to_string());
+            characters.push(Character::create_character_from_spec(spec, args));
+        }
+    }
+    for character in characters.iter_mut() {
+        character.convert_stats_and_set_cooldowns();
+    }
+    println!("Simulating {} enchant alternatives against the current \
+             enchants.", alternatives.len());
+    let all_stats = crate::run_all_iterations(&quiet_args, &characters);
+    let report = OverallStats::get_compare_report(&all_stats, &names);
+    println!("\nCurrent enchants: {:.2} ±{:.2} dps over {} iterations of \
+             {}s each, seed {}.", report.specs[0].dps.mean,
+             report.specs[0].dps.ci95, report.iterations,
+             report.fight_length, report.seed);
+    println!("Columns: dps gain ±95% interval, relative gain, significant, \
+             enchant.");
+
+    let mut best = current.clone();
+    for (i, position) in layout.positions.iter().enumerate() {
+        let mut ranked: Vec<usize> = (0..alternatives.len())
+            .filter(|j| alternatives[*j].0 == i).collect();
+        ranked.sort_by(|a, b| report.specs[*b + 1].dps.mean
+                       .partial_cmp(&report.specs[*a + 1].dps.mean).unwrap());
+        if ranked.is_empty() { continue; }
+
+        println!("\n{} (current: {}):", position.get_label(),
+                 get_enchant_display_name(&position.current, &enchants));
+        let mut best_gain = 0.0;
+        for j in ranked {
+            let spec = &report.specs[j + 1];
+            println!("{:>+9.2} ±{:<6.2}{:>+7.2}%  {:<3}  {}",
+                     spec.dps_diff.mean, spec.dps_diff.ci95,
+                     100.0 * spec.dps_diff_ratio.mean,
+                     if spec.significant { "yes" } else { "no" },
+                     get_enchant_display_name(&alternatives[j].1, &enchants));
+            if spec.dps_diff.mean > best_gain {
+                best_gain = spec.dps_diff.mean;
+                best[i] = alternatives[j].1.to_string();
+            }
+        }
+    }
+
+    let mut best_spec = base_spec.clone();
+    best_spec.enchants = get_enchant_spec(&layout, &best);
+    if best == current {
+        println!("\nThe current enchants are the best combination found.");
+    } else {
+        // the best alternatives of every position together, checked in a
+        // run of their own since enchants on both hands interact
+        let mut best_character = Character::create_character_from_spec(
+            best_spec.clone(), args);
+        best_character.convert_stats_and_set_cooldowns();
+        let mut current_character = Character::create_character_from_spec(
+            base_spec.clone(), args);
+        current_character.convert_stats_and_set_cooldowns();
+        let all_stats = crate::run_all_iterations(
+            &quiet_args, &vec![current_character, best_character]);
+        let combined = all_stats[1].get_compared_spec_report("best",
+                                                             &all_stats[0]);
+        println!("\nBest combination, {:+.2} ±{:.2} dps ({:+.2}%) against \
+                 the current enchants:", combined.dps_diff.mean,
+                 combined.dps_diff.ci95,
+                 100.0 * combined.dps_diff_ratio.mean);
+        for (i, position) in layout.positions.iter().enumerate() {
+            if best[i] != current[i] {
+                println!("{}: {} -> {}", position.get_label(),
+                         get_enchant_display_name(&current[i], &enchants),
+                         get_enchant_display_name(&best[i], &enchants));
+            }
+        }
+    }
+
+    let header = format!("enchants optimized from {}", args.spec_file);
+    match best_spec.write_to_file(&args.save_file, &header) {
+        Ok(()) => println!("Wrote the best spec to {}.", args.save_file),
+        Err(error) => eprintln!("Could not write {}: {}", args.save_file,
+                                error)
+    }
+}
+
+#[derive(Clone)]
+struct TalentBuild {
+    // points of each searched talent, in the order of get_searched_talents
+    points: Vec<i32>,
+    // points in filler talents, per tree of TALENT_TREES
+    filler_points: Vec<i32>,
+    estimate: f32
+}
+
+impl TalentBuild {
+    fn get_total_points(&self) -> i32 {
+        return self.points.iter().sum::<i32>()
+            + self.filler_points.iter().sum::<i32>();
+    }
+}
+
+fn add_talent_build(builds: &mut Vec<TalentBuild>, build: TalentBuild,
+                    n_keep: usize) {
+    // keeps the n_keep best estimates, best first, each build once
+    if builds.iter().any(|other| other.points == build.points) { return; }
+    if builds.len() >= n_keep {
+        if build.estimate <= builds[builds.len() - 1].estimate { return; }
+        builds.pop();
+    }
+    let position = builds.iter().position(|other| other.estimate
+                                          < build.estimate)
+        .unwrap_or(builds.len());
+    builds.insert(position, build);
+}
+
+fn get_tree_builds(tree: &str, searched: &[String],
+                   talent_db: &HashMap<String,TalentInfo>,
+                   values: &[Vec<f32>], n_keep: usize)
+    -> (Vec<usize>, Vec<Vec<TalentBuild>>) {
+    // every allocation of the searched talents of a tree, counted and the
+    // best ones kept per total of points
+    let indices: Vec<usize> = (0..searched.len())
+        .filter(|i| talent_db[&searched[*i]].tree == tree).collect();
+    let tree_index = get_tree_index(tree);
+    let mut builds: Vec<Vec<TalentBuild>> = (0..TALENT_POINTS + 1)
+        .map(|_| Vec::new()).collect();
+    let mut n_legal = vec![0; TALENT_POINTS as usize + 1];
+    let mut points = vec![0; searched.len()];
+    loop {
+        if let Ok(filler) = get_filler_points(tree, searched, &points,
+                                              talent_db) {
+            let mut build = TalentBuild {
+                points: points.clone(),
+                filler_points: vec![0; TALENT_TREES.len()],
+                estimate: indices.iter()
+                    .map(|i| values[*i][points[*i] as usize]).sum()
+            };
+            build.filler_points[tree_index] = filler;
+            let total = build.get_total_points();
+            if total <= TALENT_POINTS {
+                n_legal[total as usize] += 1;
+                add_talent_build(&mut builds[total as usize], build, n_keep);
+            }
+        }
+
+        // next allocation, counting with a digit per talent
+        let mut digit = 0;
+        while digit < indices.len() {
+            let i = indices[digit];
+            if points[i] < talent_db[&searched[i]].max_points {
+                points[i] += 1;
+                break;
+            }
+            points[i] = 0;
+            digit += 1;
+        }
+        if digit == indices.len() { break; }
+    }
+    return (n_legal, builds);
+}
+
+fn merge_tree_builds(a: &[Vec<TalentBuild>], b: &[Vec<TalentBuild>],
+                     n_keep: usize) -> Vec<Vec<TalentBuild>> {
+    // the trees are independent, so the best builds of each total of points
+    // come from the best builds of the two parts
+    let mut merged: Vec<Vec<TalentBuild>> = (0..TALENT_POINTS + 1)
+        .map(|_| Vec::new()).collect();
+    for (total_a, builds_a) in a.iter().enumerate() {
+        for (total_b, builds_b) in b.iter().enumerate() {
+            let total = total_a + total_b;
+            if total > TALENT_POINTS as usize { break; }
+            for build_a in builds_a {
+                for build_b in builds_b {
+                    let build = TalentBuild {
+                        points: build_a.points.iter().zip(&build_b.points)
+                            .map(|(x, y)| x + y).collect(),
+                        filler_points: build_a.filler_points.iter()
+                            .zip(&build_b.filler_points)
+                            .map(|(x, y)| x + y).collect(),
+                        estimate: build_a.estimate + build_b.estimate
+                    };
+                    add_talent_build(&mut merged[total], build, n_keep);
+                }
+            }
+        }
+    }
+    return merged;
+}
+
+fn get_talent_spec(base_spec: &CharacterSpecification, searched: &[String],
+                   points: &[i32]) -> CharacterSpecification {
+    let mut spec = base_spec.clone();
+    for (i, key) in searched.iter().enumerate() {
+        spec.talents.set_points(key, points[i]);
+    }
+    return spec;
+}
+
+fn get_tree_points_text(build: &TalentBuild, searched: &[String],
+                     talent_db: &HashMap<String,TalentInfo>) -> String {
+    let mut tree_points = build.filler_points.clone();
+    for (i, key) in searched.iter().enumerate() {
+        tree_points[get_tree_index(&talent_db[key].tree)] += build.points[i];
+    }
+    return tree_points.iter().map(|points| points.to_string())
+        .collect::<Vec<String>>().join("/");
+}
+
+fn print_talent_values(searched: &[String],
+                       talent_db: &HashMap<String,TalentInfo>,
+                       current: &[i32], indices: &[Vec<usize>],
+                       all_stats: &[OverallStats]) {
+    let widths = [28, 7, 17, 10];
+    println!("\nDps of the talent points with the rest of the current \
+             talents, ±95% interval.");
+    utils::print_table_row(&widths, &["Talent", "Points", "All points",
+                                      "Per point",
+                                      "Each point, from the first"]);
+    for (i, key) in searched.iter().enumerate() {
+        let info = &talent_db[key];
+        let n_points = indices[i].len() - 1;
+        let all = all_stats[indices[i][n_points]]
+            .get_compared_spec_report(key, &all_stats[indices[i][0]]);
+        let mut each: Vec<String> = Vec::new();
+        for k in 1..n_points + 1 {
+            let point = all_stats[indices[i][k]]
+                .get_compared_spec_report(key, &all_stats[indices[i][k - 1]]);
+            each.push(format!("{:+.2} ±{:.2}", point.dps_diff.mean,
+                              point.dps_diff.ci95));
+        }
+        let points = format!("{}/{}", current[i], info.max_points);
+        let all_points = format!("{:>+9.2} ±{:<6.2}", all.dps_diff.mean,
+                                 all.dps_diff.ci95);
+        let per_point = format!("{:+.2}", all.dps_diff.mean / n_points as f32);
+        utils::print_table_row(&widths, &[&info.name, &points, &all_points,
+                                          &per_point, &each.join("  ")]);
+    }
+}
+
+pub fn optimize_talents(args: &Args) {
+    let quiet_args = get_quiet_args(args);
+    let base_spec = CharacterSpecification::get_char_spec(args);
+    let talent_db = armory::get_talent_collection();
+    let searched = get_searched_talents(&talent_db, &base_spec.talents);
+    let current: Vec<i32> = searched.iter()
+        .map(|key| base_spec.talents.get_points(key).unwrap()).collect();
+
+    let mut current_build = TalentBuild {
+        points: current.clone(),
+        filler_points: vec![0; TALENT_TREES.len()],
+        estimate: 0.0
+    };
+    for (tree_index, tree) in TALENT_TREES.iter().enumerate() {
+        match get_filler_points(tree, &searched, &current, &talent_db) {
+            Ok(filler) => current_build.filler_points[tree_index] = filler,
+            Err(error) => println!("The current talents are not legal: {}.",
+                                   error)
+        }
+    }
+    if current_build.get_total_points() > TALENT_POINTS {
+        println!("The current talents need {} of {} points.",
+                 current_build.get_total_points(), TALENT_POINTS);
+    }
+
+    // every level of each talent, with the rest of the current talents,
+    // values the talent points
+    let mut characters = vec![Character::create_character_from_spec(
+            base_spec.clone(), args)];
+    let mut indices: Vec<Vec<usize>> = Vec::new();
+    for (i, key) in searched.iter().enumerate() {
+        let mut levels: Vec<usize> = Vec::new();
+        let max_points = utils::max_i32(talent_db[key].max_points, current[i]);
+        for k in 0..max_points + 1 {
+            if k == current[i] {
+                levels.push(0);
+                continue;
+            }
+            let mut points = current.clone();
+            points[i] = k;
+            levels.push(characters.len());
+            characters.push(Character::create_character_from_spec(
+                    get_talent_spec(&base_spec, &searched, &points), args));
+        }
+        indices.push(levels);
+    }
+    for character in characters.iter_mut() {
+        character.convert_stats_and_set_cooldowns();
+    }
+    println!("Simulating {} talent levels against the current talents.",
+             characters.len() - 1);
+    let all_stats = crate::run_all_iterations(&quiet_args, &characters);
+    let base_dps = all_stats[0].get_mean_dps();
+    let values: Vec<Vec<f32>> = indices.iter()
+        .map(|levels| levels.iter()
+             .map(|index| all_stats[*index].get_mean_dps() - base_dps)
+             .collect())
+        .collect();
+    print_talent_values(&searched, &talent_db, &current, &indices,
+                        &all_stats);
+
+    // the estimate of a build adds up the values of its talent points
+    let n_top = args.top_candidates.max(1) as usize;
+    let mut n_legal = vec![0; TALENT_POINTS as usize + 1];
+    n_legal[0] = 1;
+    let mut builds: Vec<Vec<TalentBuild>> = (0..TALENT_POINTS + 1)
+        .map(|_| Vec::new()).collect();
+    builds[0].push(TalentBuild {
+        points: vec![0; searched.len()],
+        filler_points: vec![0; TALENT_TREES.len()],
+        estimate: 0.0
+    });
+    for tree in TALENT_TREES.iter() {
+        let (n_tree, tree_builds) = get_tree_builds(tree, &searched,
+                                                    &talent_db, &values,
+                                                    n_top + 1);
+        let mut merged_n_legal = vec![0; n_legal.len()];
+        for (total_a, n_a) in n_legal.iter().enumerate() {
+            for (total_b, n_b) in n_tree.iter().enumerate() {
+                if total_a + total_b < n_legal.len() {
+                    merged_n_legal[total_a + total_b] += n_a * n_b;
+                }
+            }
+        }
+        n_legal = merged_n_legal;
+        builds = merge_tree_builds(&builds, &tree_builds, n_top + 1);
+    }
+    let mut candidates: Vec<TalentBuild> = Vec::new();
+    for build in builds.into_iter().flatten() {
+        if build.points == current { continue; }
+        add_talent_build(&mut candidates, build, n_top);
+    }
+    println!("\nEstimated {} legal builds, simulating the best {} against \
+             the current talents.", n_legal.iter().sum::<usize>(),
+             candidates.len());
+
+    let mut names = vec!["current".to_string()];
+    let mut characters = vec![Character::create_character_from_spec(
+            base_spec.clone(), args)];
+    for (i, candidate) in candidates.iter().enumerate() {
+        names.push(format!("#{}", i + 1));
+        characters.push(Character::create_character_from_spec(
+                get_talent_spec(&base_spec, &searched, &candidate.points),
+                args));
+    }
+    for character in characters.iter_mut() {
+        character.convert_stats_and_set_cooldowns();
+    }
+    let all_stats = crate::run_all_iterations(&quiet_args, &characters);
+    let report = OverallStats::get_compare_report(&all_stats, &names);
+    let estimates: Vec<f32> = candidates.iter().map(|c| c.estimate).collect();
+    print_optimizer_results(&report, &estimates, "Talents");
+
+    let mut best = 0;
+    for i in 1..report.specs.len() {
+        if report.specs[i].dps.mean > report.specs[best].dps.mean { best = i; }
+    }
+    let mut best_spec = base_spec.clone();
+    if best == 0 {
+        println!("\nNo candidate beat the current talents.");
+    } else {
+        let best_build = &candidates[best - 1];
+        best_spec = get_talent_spec(&base_spec, &searched, &best_build.points);
+        println!("\nBest talents are {}, {} points ({}) against {} ({}), \
+                 changes against the current talents:", names[best],
+                 best_build.get_total_points(),
+                 get_tree_points_text(best_build, &searched, &talent_db),
+                 current_build.get_total_points(),
+                 get_tree_points_text(&current_build, &searched, &talent_db));
+        for (i, key) in searched.iter().enumerate() {
+            if best_build.points[i] != current[i] {
+                println!("{}: {} -> {}", talent_db[key].name, current[i],
+                         best_build.points[i]);
+            }
+        }
+    }
+
+    let header = format!("talents optimized from {}", args.spec_file);
+    match best_spec.write_to_file(&args.save_file, &header) {
+        Ok(()) => println!("Wrote the best spec to {}.", args.save_file),
+        Err(error) => eprintln!("Could not write {}: {}", args.save_file,
+                                error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_build(points: Vec<i32>, estimate: f32) -> TalentBuild {
+        TalentBuild {
+            points,
+            filler_points: vec![0; TALENT_TREES.len()],
+            estimate
+        }
+    }
+
+    fn get_estimates(builds: &[TalentBuild]) -> Vec<f32> {
+        return builds.iter().map(|build| build.estimate).collect();
+    }
+
+    #[test]
+    fn talent_builds_are_kept_best_first() {
+        let mut builds = Vec::new();
+        add_talent_build(&mut builds, get_build(vec![1], 1.0), 3);
+        add_talent_build(&mut builds, get_build(vec![2], 3.0), 3);
+        add_talent_build(&mut builds, get_build(vec![3], 2.0), 3);
+        assert_eq!(get_estimates(&builds), vec![3.0, 2.0, 1.0]);
+    }
+
+    #[test]
+    fn only_the_best_talent_builds_are_kept() {
+        let mut builds = Vec::new();
+        for i in 0..5 {
+            add_talent_build(&mut builds, get_build(vec![i], i as f32), 2);
+        }
+        add_talent_build(&mut builds, get_build(vec![9], 0.5), 2);
+        assert_eq!(get_estimates(&builds), vec![4.0, 3.0]);
+    }
+
+    #[test]
+    fn talent_builds_with_equal_estimates_are_kept() {
+        let mut builds = Vec::new();
+        add_talent_build(&mut builds, get_build(vec![1, 0], 2.0), 3);
+        add_talent_build(&mut builds, get_build(vec![0, 1], 2.0), 3);
+        assert_eq!(builds.len(), 2);
+    }
+
+    #[test]
+    fn a_talent_build_is_kept_once() {
+        let mut builds = Vec::new();
+        add_talent_build(&mut builds, get_build(vec![1, 2], 2.0), 3);
+        add_talent_build(&mut builds, get_build(vec![1, 2], 2.0), 3);
+        assert_eq!(builds.len(), 1);
+    }
+}
@@ -0,0 +1,167 @@
+/* Machine readable simulation results, printed with `--output json`.
+ *
+ * The layout of SimulationReport is the stable schema consumed by the web
+ * front end. Fields are only ever added, and SCHEMA_VERSION is bumped when
+ * an existing field changes meaning. All ratios are fractions (0.0 - 1.0),
+ * all "ci95" values are half widths of the 95% confidence interval.
+ */
+use serde::Serialize;
+
+pub const SCHEMA_VERSION: i32 = 1;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json
+}
+
+#[derive(Debug,Serialize)]
+pub struct MeanWithError {
+    pub mean: f32,
+    pub ci95: f32
+}
+
+#[derive(Debug,Serialize)]
+pub struct DpsPercentiles {
+    pub min: f32,
+    pub p5: f32,
+    pub median: f32,
+    pub p95: f32,
+    pub max: f32
+}
+
+#[derive(Debug,Serialize)]
+pub struct NamedRatio {
+    pub name: String,
+    pub mean: f32,
+    pub ci95: f32
+}
+
+#[derive(Debug,Serialize)]
+pub struct NamedValue {
+    pub name: String,
+    pub value: f32
+}
+
+#[derive(Debug,Serialize)]
+pub struct HitTypeCounts {
+    pub hit: i32,
+    pub crit: i32,
+    pub glancing: i32,
+    pub miss: i32,
+    pub dodge: i32,
+    pub parry: i32,
+    pub block: i32
+}
+
+#[derive(Debug,Serialize)]
+pub struct AbilityReport {
+    pub name: String,
+    // summed over all iterations
+    pub total_count: i32,
+    pub total_dmg: f32,
+    // over the attacks that did damage, zero if none did
+    pub min_dmg: f32,
+    pub avg_dmg: f32,
+    pub max_dmg: f32,
+    pub casts_per_minute: f32,
+    // average of the landed attacks that did not crit, glancing and blocked
+    // ones included, and of the crits, zero if there were none
+    pub avg_hit_dmg: f32,
+    pub avg_crit_dmg: f32,
+    // fractions of all attempts
+    pub crit_rate: f32,
+    pub miss_rate: f32,
+    pub dodge_rate: f32,
+    pub parry_rate: f32,
+    // zero for white hits and proccs, which cost no energy
+    pub energy_spent: i32,
+    pub dmg_per_energy: f32,
+    pub hit_types: HitTypeCounts
+}
+
+#[derive(Debug,Serialize)]
+pub struct ResourceReport {
+    // energy lost to the energy cap per fight, by source
+    pub energy_wasted: Vec<NamedRatio>,
+    // share of the fight spent at the energy cap
+    pub time_at_energy_cap: MeanWithError,
+    // time weighted
+    pub avg_energy: MeanWithError,
+    // combo points gained while already at 5, per fight
+    pub combo_points_wasted: MeanWithError
+}
+
+#[derive(Debug,Serialize)]
+pub struct TimelineBinReport {
+    // seconds into the fight
+    pub start: f32,
+    pub end: f32,
+    // dps within the bin, and from the pull until the end of the bin
+    pub dps: f32,
+    pub cumulative_dps: f32,
+    // time weighted within the bin
+    pub avg_energy: f32,
+    // share of the bin each buff or cooldown was active
+    pub buff_uptimes: Vec<NamedValue>
+}
+
+#[derive(Debug,Serialize)]
+pub struct StatWeightReport {
+    // e.g. "+10 agility"
+    pub name: String,
+    // mean dps difference against the default run, paired per iteration
+    pub dps_diff: MeanWithError,
+    // the same difference relative to the default dps
+    pub dps_diff_ratio: MeanWithError
+}
+
+#[derive(Debug,Serialize)]
+pub struct SimulationReport {
+    pub schema_version: i32,
+    pub iterations: i32,
+    pub fight_length: f32,
+    pub seed: u64,
+    pub dps: MeanWithError,
+    // spread of the dps of single iterations
+    pub dps_percentiles: DpsPercentiles,
+    // share of the total damage done by each ability
+    pub damage_shares: Vec<NamedRatio>,
+    // share of the total damage done by each procc, extra attacks included
+    pub procc_damage_shares: Vec<NamedRatio>,
+    // share of the fight each buff or cooldown was active
+    pub buff_uptimes: Vec<NamedRatio>,
+    // times each cooldown was used per fight
+    pub cooldown_activations: Vec<NamedRatio>,
+    pub resources: ResourceReport,
+    // empty unless a timeline bin width was given
+    pub timeline: Vec<TimelineBinReport>,
+    pub abilities: Vec<AbilityReport>,
+    // empty unless stat weights were requested
+    pub stat_weights: Vec<StatWeightReport>
+}
+
+#[derive(Debug,Serialize)]
+pub struct ComparedSpecReport {
+    pub spec_file: String,
+    pub dps: MeanWithError,
+    // against the first specs file, paired per iteration, zero for itself
+    pub dps_diff: MeanWithError,
+    pub dps_diff_ratio: MeanWithError,
+    // the 95% confidence interval of the difference excludes zero
+    pub significant: bool
+}
+
+#[derive(Debug,Serialize)]
+pub struct CompareReport {
+    pub schema_version: i32,
+    pub iterations: i32,
+    pub fight_length: f32,
+    pub seed: u64,
+    pub specs: Vec<ComparedSpecReport>
+}
+
+pub fn print_json<T: Serialize>(report: &T) {
+    println!("{}", serde_json::to_string_pretty(report)
+             .expect("Could not serialize the simulation report"));
+}
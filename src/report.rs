@@ -0,0 +1,322 @@
+/* Self-contained html report, written with `--report out.html`.
+ *
+ * Everything, charts included, is inlined in one file so that it can be
+ * shared and opened offline. The charts are plain SVG drawn from the same
+ * numbers as the text and json output.
+ */
+use crate::armory::Character;
+use crate::output::{SimulationReport,StatWeightReport};
+use crate::stats::OverallStats;
+
+use std::f32::consts::PI;
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::{BufWriter,Write};
+
+const CHART_WIDTH: f32 = 640.0;
+const CHART_HEIGHT: f32 = 240.0;
+const MARGIN: f32 = 40.0;
+const COLORS: [&str; 10] = ["#4e79a7", "#f28e2b", "#e15759", "#76b7b2",
+    "#59a14f", "#edc948", "#b07aa1", "#ff9da7", "#9c755f", "#bab0ac"];
+
+const STYLE: &str = "body { font-family: sans-serif; max-width: 760px; \
+margin: 2em auto; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+td, th { padding: 2px 12px 2px 0; text-align: left; }
+svg text { font-size: 11px; fill: #222; }
+.columns { display: flex; gap: 2em; }";
+
+pub fn write_html_report(path: &str, character: &Character,
+                         stats: &OverallStats,
+                         stat_weights: Vec<StatWeightReport>)
+    -> std::io::Result<()> {
+
+    let mut report = stats.get_report();
+    report.stat_weights = stat_weights;
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+                  <title>Rogue simulation report</title>\n");
+    writeln!(html, "<style>\n{}\n</style>\n</head>\n<body>", STYLE).unwrap();
+    html.push_str("<h1>Rogue simulation report</h1>\n");
+    writeln!(html, "<p>{:.2} ±{:.2} dps over {} iterations of {}s, \
+             seed {}.</p>", report.dps.mean, report.dps.ci95,
+             report.iterations, report.fight_length, report.seed).unwrap();
+
+    html.push_str(&get_character_summary(character));
+
+    html.push_str("<h2>Dps distribution</h2>\n");
+    html.push_str(&get_histogram_svg(&stats.get_dps_histogram()));
+    writeln!(html, "<p>Min {:.0}, 5th percentile {:.0}, median {:.0}, \
+             95th percentile {:.0}, max {:.0}.</p>",
+             report.dps_percentiles.min, report.dps_percentiles.p5,
+             report.dps_percentiles.median, report.dps_percentiles.p95,
+             report.dps_percentiles.max).unwrap();
+
+    html.push_str("<h2>Damage by ability</h2>\n");
+    html.push_str(&get_pie_chart_svg(&report));
+
+    if ! report.timeline.is_empty() {
+        html.push_str("<h2>Dps over the fight</h2>\n");
+        html.push_str(&get_timeline_svg(&report));
+    }
+
+    if ! report.stat_weights.is_empty() {
+        html.push_str("<h2>Stat weights</h2>\n");
+        html.push_str(&get_stat_weight_svg(&report.stat_weights));
+    }
+
+    html.push_str("</body>\n</html>\n");
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(html.as_bytes())?;
+    return Ok(());
+}
+
+fn escape(text: &str) -> String {
+    return text.replace('&', "&amp;").replace('<', "&lt;")
+        .replace('>', "&gt;").replace('"', "&quot;");
+}
+
+fn get_table(rows: &Vec<(String, String)>) -> String {
+    let mut table = String::from("<table>\n");
+    for (name, value) in rows {
+        writeln!(table, "<tr><td>{}</td><td>{}</td></tr>",
+                 escape(name), escape(value)).unwrap();
+    }
+    table.push_str("</table>\n");
+    return table;
+}
+
+fn get_character_summary(character: &Character) -> String {
+    let prim = &character.prim_stats;
+    let sec = &character.sec_stats;
+    let stats = vec![
+        ("Strength".to_string(), prim.strength.to_string()),
+        ("Agility".to_string(), prim.agility.to_string()),
+        ("Sword skill".to_string(), prim.sword_skill.to_string()),
+        ("Dagger skill".to_string(), prim.dagger_skill.to_string()),
+        ("Attack power".to_string(), sec.attack_power.to_string()),
+        ("Crit".to_string(), format!("{:.2}%", 100.0 * sec.crit)),
+        ("Hit".to_string(), format!("{:.2}%", 100.0 * sec.hit)),
+        ("Haste".to_string(), format!("{:.2}%", 100.0 * sec.haste))
+    ];
+
+    let mut gear = vec![
+        ("MH".to_string(), character.mh.get_name().to_string()),
+        ("OH".to_string(), character.oh.get_name().to_string())
+    ];
+    for armor in &character.armor {
+        gear.push((armor.get_slot_name(), armor.get_name().to_string()));
+    }
+    let enchant_lists = vec![("Armor enchant", &character.armor_enchants),
+                             ("MH enchant", &character.mh_enchants),
+                             ("OH enchant", &character.oh_enchants)];
+    for (slot, enchants) in enchant_lists {
+        for enchant in enchants.iter() {
+            gear.push((slot.to_string(), enchant.name.to_string()));
+        }
+    }
+
+    // only the talents with points in them
+    let mut talents = Vec::new();
+    let talent_values = serde_json::to_value(&character.talents)
+        .expect("Could not serialize the talents");
+    if let Some(talent_map) = talent_values.as_object() {
+        for (name, points) in talent_map {
+            if points.as_i64().unwrap_or(0) > 0 {
+                talents.push((name.replace('_', " "), points.to_string()));
+            }
+        }
+    }
+
+    let mut summary = String::from("<h2>Character</h2>\n\
+                                   <div class=\"columns\">\n<div>\n\
+                                   <h3>Stats</h3>\n");
+    summary.push_str(&get_table(&stats));
+    summary.push_str("<h3>Talents</h3>\n");
+    summary.push_str(&get_table(&talents));
+    summary.push_str("</div>\n<div>\n<h3>Gear</h3>\n");
+    summary.push_str(&get_table(&gear));
+    summary.push_str("</div>\n</div>\n");
+    return summary;
+}
+
+fn get_svg_start(height: f32) -> String {
+    return format!("<svg xmlns=\"http://www.w3.org/2000/svg\" \
+                   width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+                   w = CHART_WIDTH, h = height);
+}
+
+fn get_axes(svg: &mut String, x_label: &str, y_label: &str, y_max: f32) {
+    let bottom = CHART_HEIGHT - MARGIN;
+    writeln!(svg, "<line x1=\"{m}\" y1=\"{m2}\" x2=\"{m}\" y2=\"{b}\" \
+             stroke=\"#222\"/>\n<line x1=\"{m}\" y1=\"{b}\" x2=\"{r}\" \
+             y2=\"{b}\" stroke=\"#222\"/>", m = MARGIN, m2 = MARGIN / 2.0,
+             b = bottom, r = CHART_WIDTH - MARGIN / 2.0).unwrap();
+    writeln!(svg, "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+             CHART_WIDTH / 2.0, CHART_HEIGHT - 5.0, escape(x_label)).unwrap();
+    writeln!(svg, "<text x=\"{}\" y=\"{}\">{}</text>", 5.0, MARGIN / 2.0 - 5.0,
+             escape(y_label)).unwrap();
+    writeln!(svg, "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{:.0}</text>",
+             MARGIN - 3.0, MARGIN / 2.0 + 4.0, y_max).unwrap();
+}
+
+fn get_histogram_svg(histogram: &[(f32, f32, usize)]) -> String {
+    if histogram.is_empty() {
+        return "<p>All iterations gave the same dps.</p>\n".to_string();
+    }
+    let max_count = histogram.iter().map(|bin| bin.2).max().unwrap() as f32;
+    let plot_width = CHART_WIDTH - 1.5 * MARGIN;
+    let plot_height = CHART_HEIGHT - 1.5 * MARGIN;
+    let bar_width = plot_width / histogram.len() as f32;
+
+    let mut svg = get_svg_start(CHART_HEIGHT);
+    for (i, (start, end, count)) in histogram.iter().enumerate() {
+        let height = plot_height * *count as f32 / max_count;
+        writeln!(svg, "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" \
+                 height=\"{:.1}\" fill=\"{}\"><title>{:.0} - {:.0} dps: {} \
+                 iterations</title></rect>",
+                 MARGIN + i as f32 * bar_width + 1.0,
+                 CHART_HEIGHT - MARGIN - height, bar_width - 2.0, height,
+                 COLORS[0], start, end, count).unwrap();
+    }
+    // label the outer edges and the middle of the dps range
+    let first = histogram.first().unwrap().0;
+    let last = histogram.last().unwrap().1;
+    for fraction in &[0.0, 0.5, 1.0] {
+        writeln!(svg, "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">\
+                 {:.0}</text>", MARGIN + fraction * plot_width,
+                 CHART_HEIGHT - MARGIN + 14.0,
+                 first + fraction * (last - first)).unwrap();
+    }
+    get_axes(&mut svg, "Dps", "Iterations", max_count);
+    svg.push_str("</svg>\n");
+    return svg;
+}
+
+fn get_pie_chart_svg(report: &SimulationReport) -> String {
+    let mut slices: Vec<(&str, f32)> = Vec::new();
+    for share in report.damage_shares.iter()
+        .chain(report.procc_damage_shares.iter()) {
+        if share.mean > 0.0 { slices.push((&share.name, share.mean)); }
+    }
+    slices.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    let total: f32 = slices.iter().map(|slice| slice.1).sum();
+    if total <= 0.0 { return "<p>No damage was done.</p>\n".to_string(); }
+
+    let radius = CHART_HEIGHT / 2.0 - 10.0;
+    let (cx, cy) = (CHART_HEIGHT / 2.0, CHART_HEIGHT / 2.0);
+    let height = CHART_HEIGHT.max(30.0 + 18.0 * slices.len() as f32);
+    let mut svg = get_svg_start(height);
+    let mut angle: f32 = 0.0;
+    for (i, (name, share)) in slices.iter().enumerate() {
+        let color = COLORS[i % COLORS.len()];
+        let fraction = share / total;
+        let title = format!("<title>{}: {:.1}%</title>", escape(name),
+                            100.0 * fraction);
+        if fraction >= 0.9999 {
+            // a single slice can not be drawn as an arc
+            writeln!(svg, "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\">\
+                     {}</circle>", cx, cy, radius, color, title).unwrap();
+        } else {
+            let next_angle = angle + 2.0 * PI * fraction;
+            let large_arc = if fraction > 0.5 { 1 } else { 0 };
+            writeln!(svg, "<path d=\"M {cx} {cy} L {:.2} {:.2} A {r} {r} 0 \
+                     {} 1 {:.2} {:.2} Z\" fill=\"{}\">{}</path>",
+                     cx + radius * angle.sin(), cy - radius * angle.cos(),
+                     large_arc, cx + radius * next_angle.sin(),
+                     cy - radius * next_angle.cos(), color, title,
+                     cx = cx, cy = cy, r = radius).unwrap();
+            angle = next_angle;
+        }
+        // legend to the right of the pie
+        let y = 20.0 + 18.0 * i as f32;
+        writeln!(svg, "<rect x=\"{}\" y=\"{}\" width=\"12\" height=\"12\" \
+                 fill=\"{}\"/><text x=\"{}\" y=\"{}\">{} {:.1}%</text>",
+                 CHART_HEIGHT + 20.0, y - 10.0, color, CHART_HEIGHT + 38.0,
+                 y, escape(name), 100.0 * fraction).unwrap();
+    }
+    svg.push_str("</svg>\n");
+    return svg;
+}
+
+fn get_timeline_svg(report: &SimulationReport) -> String {
+    let max_dps = report.timeline.iter()
+        .map(|bin| bin.dps.max(bin.cumulative_dps))
+        .fold(0.0, f32::max);
+    if max_dps <= 0.0 { return "<p>No damage was done.</p>\n".to_string(); }
+    let plot_width = CHART_WIDTH - 1.5 * MARGIN;
+    let plot_height = CHART_HEIGHT - 1.5 * MARGIN;
+    let x_of = |time: f32| MARGIN + plot_width * time / report.fight_length;
+    let y_of = |dps: f32| CHART_HEIGHT - MARGIN - plot_height * dps / max_dps;
+
+    // dps within each bin as steps, cumulative dps at the bin ends
+    let mut bin_points = String::new();
+    let mut cumulative_points = format!("{:.1},{:.1} ", x_of(0.0), y_of(0.0));
+    for bin in &report.timeline {
+        write!(bin_points, "{:.1},{:.1} {:.1},{:.1} ", x_of(bin.start),
+               y_of(bin.dps), x_of(bin.end), y_of(bin.dps)).unwrap();
+        write!(cumulative_points, "{:.1},{:.1} ", x_of(bin.end),
+               y_of(bin.cumulative_dps)).unwrap();
+    }
+
+    let mut svg = get_svg_start(CHART_HEIGHT);
+    writeln!(svg, "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" \
+             stroke-width=\"2\"/>", bin_points.trim(), COLORS[0]).unwrap();
+    writeln!(svg, "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" \
+             stroke-width=\"2\" stroke-dasharray=\"6 3\"/>",
+             cumulative_points.trim(), COLORS[1]).unwrap();
+    for fraction in &[0.0, 0.5, 1.0] {
+        writeln!(svg, "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">\
+                 {:.0}s</text>", MARGIN + fraction * plot_width,
+                 CHART_HEIGHT - MARGIN + 14.0,
+                 fraction * report.fight_length).unwrap();
+    }
+    writeln!(svg, "<text x=\"{}\" y=\"{}\" fill=\"{}\">dps in bin</text>\
+             <text x=\"{}\" y=\"{}\" fill=\"{}\">cumulative dps</text>",
+             CHART_WIDTH - 200.0, MARGIN / 2.0, COLORS[0],
+             CHART_WIDTH - 120.0, MARGIN / 2.0, COLORS[1]).unwrap();
+    get_axes(&mut svg, "Time", "Dps", max_dps);
+    svg.push_str("</svg>\n");
+    return svg;
+}
+
+fn get_stat_weight_svg(stat_weights: &[StatWeightReport]) -> String {
+    let row_height = 22.0;
+    let label_width = 160.0;
+    let height = row_height * stat_weights.len() as f32 + 10.0;
+    let max_diff = stat_weights.iter()
+        .map(|weight| weight.dps_diff.mean.abs() + weight.dps_diff.ci95)
+        .fold(0.0, f32::max);
+    if max_diff <= 0.0 { return "<p>No stat changed the dps.</p>\n".to_string(); }
+
+    // bars grow left or right from the zero line, with the 95% interval
+    let half_width = (CHART_WIDTH - label_width - 130.0) / 2.0;
+    let zero = label_width + half_width;
+    let scale = half_width / max_diff;
+
+    let mut svg = get_svg_start(height);
+    for (i, weight) in stat_weights.iter().enumerate() {
+        let y = 5.0 + i as f32 * row_height;
+        let diff = weight.dps_diff.mean;
+        let color = if diff >= 0.0 { COLORS[4] } else { COLORS[2] };
+        writeln!(svg, "<text x=\"0\" y=\"{:.1}\">{}</text>",
+                 y + 14.0, escape(&weight.name)).unwrap();
+        writeln!(svg, "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" \
+                 height=\"{:.1}\" fill=\"{}\"/>", zero + scale * diff.min(0.0),
+                 y + 3.0, scale * diff.abs(), row_height - 6.0, color).unwrap();
+        writeln!(svg, "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" \
+                 y2=\"{:.1}\" stroke=\"#222\"/>",
+                 zero + scale * (diff - weight.dps_diff.ci95), y + row_height / 2.0,
+                 zero + scale * (diff + weight.dps_diff.ci95),
+                 y + row_height / 2.0).unwrap();
+        writeln!(svg, "<text x=\"{:.1}\" y=\"{:.1}\">{:+.2} ±{:.2} dps\
+                 </text>", CHART_WIDTH - 110.0, y + 14.0, diff,
+                 weight.dps_diff.ci95).unwrap();
+    }
+    writeln!(svg, "<line x1=\"{z}\" y1=\"0\" x2=\"{z}\" y2=\"{h}\" \
+             stroke=\"#222\"/>", z = zero, h = height).unwrap();
+    svg.push_str("</svg>\n");
+    return svg;
+}
@@ -1,9 +1,21 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
 use std::fmt::Display;
-use crate::utils::{Args,min_f32,max_f32,min_i32,max_i32,roll_die};
-use crate::armory::{Character,Cooldown,HitProcc,SpecialBonus,Weapon,WeaponType};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use crate::utils::{Args,max_f32,min_i32,max_i32,roll_die,roll_between};
+use crate::armory::{Character,Cooldown,HitProcc,PrimStats,Race,SecStats,
+SpecialBonus,Target,Weapon,WeaponType};
 use crate::armory::CooldownEffect::{EnergyRegenMultiplier,AttackSpeedMultiplier,
 InstantEnergyRefill};
 use crate::stats::CurrentStats;
+use crate::combat_log;
+use crate::combat_log::{CombatEvent,CombatLog,ProccEffect,ENERGY_SPENT,
+ENERGY_TICKS,RUTHLESSNESS};
+use serde::Serialize;
+
+const SLICE_AND_DICE: &str = "Slice and dice";
+const RELENTLESS_STRIKES: &str = "Relentless strikes";
 
 
 #[derive(Debug)]
@@ -17,10 +29,15 @@ pub struct Simulator {
     modifiers: Modifiers,
     cooldowns: Vec<Cooldown>,
     active_buffs: ActiveBuffs,
+    char_state: CharacterState,
     stats: CurrentStats,
-    extra_attacks: i32,
+    extra_attacks: Vec<String>,
     energy: i32,
+    energy_since: f32, // when energy last changed
     combo_points: i32,
+    rng: StdRng,
+    seed: u64,
+    log: CombatLog,
     verb: i32,
     stat_weights: bool
 }
@@ -37,26 +54,30 @@ impl Simulator {
             modifiers: Modifiers::new(),
             cooldowns: Vec::new(),
             active_buffs: ActiveBuffs::new(),
+            char_state: CharacterState::new(),
             stats: CurrentStats::new(),
-            extra_attacks: 0,
+            extra_attacks: Vec::new(),
             energy: 0,
+            energy_since: 0.0,
             combo_points: 0,
+            rng: StdRng::seed_from_u64(0),
+            seed: 0,
+            log: CombatLog::new(),
             verb: 0,
             stat_weights: false
         }
     }
 
     pub fn apply_input_arguments(&mut self, args: &Args) {
-        self.timekeep.dt = args.dt;
         self.timekeep.fight_length = args.fight_length;
         self.fight_length = args.fight_length;
         self.stats.set_fight_length(args.fight_length);
+        self.stats.set_timeline_bin_width(args.timeline_bin_width);
+        self.seed = args.seed;
         self.verb = args.verb;
-        self.timekeep.verb = args.verb;
-        self.mh.enemy_lvl = args.enemy_lvl;
-        self.oh.enemy_lvl = args.enemy_lvl;
         self.stat_weights = args.weights;
-        self.timekeep.stat_weights = args.weights;
+        self.log.set_logger(combat_log::create_logger(args),
+                            args.fight_length);
     }
 
     pub fn get_stats(&self) -> CurrentStats {
@@ -65,7 +86,7 @@ impl Simulator {
 
     fn get_glancing_red_factor_from_skill_delta(&self, skill_delta: i32) -> f32 {
         let glancing_red_factor = match skill_delta {
-            15 => 1.0 - 0.35,
+            15..=i32::MAX => 1.0 - 0.35,
             14 => 1.0 - 0.31,
             13 => 1.0 - 0.27,
             12 => 1.0 - 0.23,
@@ -73,16 +94,15 @@ impl Simulator {
             10 => 1.0 - 0.15,
             9  => 1.0 - 0.11,
             8  => 1.0 - 0.07,
-            -300..=7 =>  1.0 - 0.05,
-            _ => panic!("Skill delta not implemented")
+            i32::MIN..=7 =>  1.0 - 0.05
         };
         return glancing_red_factor;
     }
 
     fn set_glancing_reduction(&mut self, character: &Character) {
         // Main hand
-        let skill_delta_mh = self.mh.get_skill_delta(character);
-        let skill_delta_oh = self.oh.get_skill_delta(character);
+        let skill_delta_mh = self.mh.get_skill_delta(&character.prim_stats);
+        let skill_delta_oh = self.oh.get_skill_delta(&character.prim_stats);
 
         self.modifiers.hit.glancing_mh =
             self.get_glancing_red_factor_from_skill_delta(skill_delta_mh);
@@ -92,6 +112,9 @@ impl Simulator {
     }
 
     pub fn configure_with_character(&mut self, character: &Character) {
+        self.char_state.set_from_character(character);
+        self.mh.set_target(&character.target);
+        self.oh.set_target(&character.target);
         self.timekeep.set_mh_swing_interval(&character.mh);
         self.timekeep.set_oh_swing_interval(&character.oh);
 
@@ -104,21 +127,43 @@ impl Simulator {
         self.oh.set_mechanics_from_character(character);
 
         self.modifiers.set_modifiers(character);
+        self.modifiers.set_target_modifiers(&character.target);
 
-        self.declare_proccs();
         self.set_cooldowns(character);
         self.set_glancing_reduction(character);
         self.incorporate_talents(character);
+        self.declare_proccs();
         self.set_rotation();
     }
 
+    fn recalculate_stats(&mut self) {
+        self.mh.set_mechanics_from_stats(&self.char_state.prim_stats,
+                                         &self.char_state.sec_stats);
+        self.oh.set_mechanics_from_stats(&self.char_state.prim_stats,
+                                         &self.char_state.sec_stats);
+    }
+
+    fn change_attack_speed(&mut self, mult: f32) {
+        self.modifiers.general.attack_speed_modifier *= mult;
+        self.timekeep.rescale_swing_timers(mult);
+    }
+
     fn set_cooldowns(&mut self, character: &Character) {
         self.cooldowns = character.cooldowns.clone();
     }
 
     fn declare_proccs(&mut self) {
-        self.stats.declare_proccs(&self.mh.hit_proccs);
-        self.stats.declare_proccs(&self.oh.hit_proccs);
+        self.stats.declare_proccs(&self.mh.hit_proccs, WeaponSlot::Mh);
+        self.stats.declare_proccs(&self.oh.hit_proccs, WeaponSlot::Oh);
+        self.stats.declare_buff_uptime(SLICE_AND_DICE);
+        self.stats.declare_energy_source(ENERGY_TICKS);
+        self.stats.declare_energy_source(RELENTLESS_STRIKES);
+        for cd in &self.cooldowns {
+            self.stats.declare_cooldown(&cd.name, cd.effect.has_duration());
+            if let InstantEnergyRefill(_) = cd.effect {
+                self.stats.declare_energy_source(&cd.name);
+            }
+        }
     }
 
     fn incorporate_talents(&mut self, character: &Character) {
@@ -132,12 +177,9 @@ impl Simulator {
         // self.mh.add_crit(0.01 * character.talents.malice as f32);
         // self.oh.add_crit(0.01 * character.talents.malice as f32);
 
-        // relentless strikes
-        match character.talents.relentless_strikes {
-            1 => self.modifiers.finisher.restore_energy_chance_per_combo_point =
-                0.2,
-            0 => (),
-            _ => panic!("Relentless strikes can only have one talent point")
+        // relentless strikes, talent points are validated with the specs file
+        if character.talents.relentless_strikes > 0 {
+            self.modifiers.finisher.restore_energy_chance_per_combo_point = 0.2;
         }
 
         // ruthlessness
@@ -157,14 +199,13 @@ impl Simulator {
         // combat table
         // imp sinister strike
         match character.talents.improved_sinister_strike {
-            0 => (),
             1 => self.ability_costs.sinister_strike -= 3,
             2 => self.ability_costs.sinister_strike -= 5,
-            _ => panic!("Illegal value of improved sinister strike")
+            _ => ()
         }
 
         // imp backstab
-        self.mh.hit_table_backstab.add_crit(
+        self.mh.add_backstab_crit(
             0.1 * character.talents.improved_backstab as f32);
 
         // precision
@@ -186,8 +227,17 @@ impl Simulator {
             1.0 + 0.1 * character.talents.dual_wield_specialization as f32;
 
         // sword specialization
+        // proccs from either hand give an extra main hand swing
         if character.talents.sword_specialization > 0 {
-            panic!("Sword specialization not implemented yet.");
+            let procc = HitProcc::ExtraAttack(
+                "Sword specialization".to_string(),
+                0.01 * character.talents.sword_specialization as f32);
+            if self.mh.weapon_type == WeaponType::Sword {
+                self.mh.hit_proccs.push(procc.clone());
+            }
+            if self.oh.weapon_type == WeaponType::Sword {
+                self.oh.hit_proccs.push(procc);
+            }
         }
 
         // aggression
@@ -204,7 +254,8 @@ impl Simulator {
 
 
     fn set_rotation(&mut self) {
-        if self.mh.weapon_type == WeaponType::Dagger {
+        // backstab is only possible from behind the target
+        if self.mh.weapon_type == WeaponType::Dagger && self.mh.behind_target {
             self.rotation = Rotation::BackstabEvis;
         } else {
             self.rotation = Rotation::SinStrikeEvis;
@@ -212,7 +263,7 @@ impl Simulator {
     }
 
     fn perform_apt_yellow_ability(&mut self) {
-        if self.timekeep.timers.global_cd > 0.0 { return; }
+        if self.timekeep.global_cd_is_active() { return; }
         if self.rotation == Rotation::BackstabEvis {
             self.backstab_evis_rotation();
         } else if self.rotation == Rotation::SinStrikeEvis {
@@ -221,51 +272,98 @@ impl Simulator {
     }
 
     fn sin_strike_evis_rotation(&mut self) {
+        let can_sinister_strike =
+            self.energy >= self.ability_costs.sinister_strike;
+        let can_eviscerate = self.energy >= self.ability_costs.eviscerate;
+        let can_slice_and_dice =
+            self.energy >= self.ability_costs.slice_and_dice;
+        let active_slice_and_dice = self.timekeep.slice_and_dice_is_active();
+
+        if self.combo_points == 2 && ! active_slice_and_dice
+            && can_slice_and_dice { self.slice_and_dice() }
+        else if self.combo_points < 5 && can_sinister_strike {
+            self.sinister_strike();
+        }
+        else if self.combo_points == 5 && ! active_slice_and_dice
+            && can_slice_and_dice { self.slice_and_dice(); }
+        else if self.combo_points == 5 && active_slice_and_dice
+            && can_eviscerate { self.eviscerate(); }
     }
 
-    fn print_slice_and_dice(&self) {
-        if self.verb > 0 && ! self.stat_weights {
-            let msg = format!("{:.1}: Slice and dice applied for {:.1}s",
-                              self.timekeep.timers.time_left,
-                              self.timekeep.timers.slice_and_dice);
-            println!("{}", msg);
+    fn sinister_strike(&mut self) {
+        let hit: Hit = self.mh.hit_table_yellow.roll_for_hit(&mut self.rng);
+        let mut dmg = 0.0;
+        let mut energy_cost = 0;
+        if hit == Hit::Miss || hit == Hit::Dodge || hit == Hit::Parry {
+            energy_cost =
+                (0.2 * self.ability_costs.sinister_strike as f32) as i32;
+            self.subtract_energy(energy_cost);
         }
+        if hit == Hit::Hit || hit == Hit::Crit || hit == Hit::Block {
+            self.trigger_hit_procc_mh();
+            energy_cost = self.ability_costs.sinister_strike;
+            self.subtract_energy(energy_cost);
+            self.add_combo_point("Sinister strike");
+            dmg = self.mh.roll_yellow_dmg(&mut self.rng) + 68.0;
+            dmg *= self.modifiers.hit.sinister_strike;
+
+            if hit == Hit::Crit {
+                dmg += dmg * self.modifiers.crit.sinister_strike;
+            }
+        }
+        dmg = self.modifiers.armor_reduction(dmg);
+        if hit == Hit::Block { dmg = self.modifiers.block_reduction(dmg); }
+        self.stats.record_sinister_strike_dmg_and_hit(dmg, &hit, energy_cost);
+        self.start_global_cd();
+
+        self.log.log(self.timekeep.now(), CombatEvent::Ability {
+            name: "Sinister strike", hit, dmg });
     }
 
     fn subtract_energy(&mut self, energy: i32) {
+        self.record_energy_level();
         self.energy = max_i32(0, self.energy - energy);
-        self.print_subtract_energy(energy);
+        if energy > 0 {
+            self.log.log(self.timekeep.now(), CombatEvent::EnergyChanged {
+                change: -energy, energy: self.energy, source: ENERGY_SPENT });
+        }
     }
 
-    fn print_subtract_energy(&self, energy: i32) {
-        if energy > 0 && self.verb > 1 && ! self.stat_weights {
-            let msg = format!("{:.1}: Energy down to {}.",
-                              self.timekeep.timers.time_left,
-                              self.energy);
-            println!("{}", msg);
-        }
+    fn record_energy_level(&mut self) {
+        // called before every change of energy, as the time weighted
+        // average needs to know how long the old level lasted
+        let duration = self.timekeep.now() - self.energy_since;
+        let at_cap = self.energy >= self.modifiers.general.energy_max;
+        self.stats.record_energy_level(self.energy, duration, at_cap);
+        self.energy_since = self.timekeep.now();
     }
 
-    fn add_energy(&mut self, energy_refill: i32) {
+    fn add_energy(&mut self, energy_refill: i32, source: &str) {
+        self.record_energy_level();
+        let wasted = max_i32(0, self.energy + energy_refill
+                             - self.modifiers.general.energy_max);
+        if wasted > 0 { self.stats.record_energy_wasted(source, wasted); }
         self.energy = min_i32(self.modifiers.general.energy_max,
                               self.energy + energy_refill);
+        self.log.log(self.timekeep.now(), CombatEvent::EnergyChanged {
+            change: energy_refill, energy: self.energy, source });
     }
 
     fn eviscerate(&mut self) {
-        let hit: Hit = self.mh.hit_table_yellow.roll_for_hit();
+        let hit: Hit = self.mh.hit_table_yellow.roll_for_hit(&mut self.rng);
         let mut dmg;
 
         self.subtract_energy(self.ability_costs.eviscerate);
         self.start_global_cd();
 
-        if self.combo_points == 1 { dmg = 247.0; }
-        else if self.combo_points == 2 { dmg = 398.0; }
-        else if self.combo_points == 3 { dmg = 549.0; }
-        else if self.combo_points == 4 { dmg = 700.0; }
-        else if self.combo_points == 5 { dmg = 851.0; }
+        if self.combo_points == 1 { dmg = roll_between(&mut self.rng, 199.0, 295.0); }
+        else if self.combo_points == 2 { dmg = roll_between(&mut self.rng, 350.0, 446.0); }
+        else if self.combo_points == 3 { dmg = roll_between(&mut self.rng, 501.0, 597.0); }
+        else if self.combo_points == 4 { dmg = roll_between(&mut self.rng, 652.0, 748.0); }
+        else if self.combo_points == 5 { dmg = roll_between(&mut self.rng, 803.0, 899.0); }
         else { panic!("Can only eviscerate with 1-5 combo points."); }
 
-        if hit == Hit::Hit || hit == Hit::Crit {
+        if hit == Hit::Hit || hit == Hit::Crit || hit == Hit::Block {
             self.trigger_hit_procc_mh();
             self.clear_combo_points_and_roll_for_finisher_procs();
 
@@ -274,19 +372,14 @@ impl Simulator {
             if hit == Hit::Crit {
                 dmg += dmg * self.modifiers.crit.eviscerate;
             }
-        }
+        } else { dmg = 0.0; }
 
         dmg = self.modifiers.armor_reduction(dmg);
-        self.stats.record_eviscerate_dmg_and_hit(dmg, &hit);
-
-    }
-
-    fn print_evis_hit_and_dmg(&self, hit: Hit, dmg: f32) {
-        if self.verb > 0 && ! self.stat_weights {
-            let msg = format!("{:.1}: Eviscerate {} for {:.0} dmg.",
-                              self.timekeep.timers.time_left, hit, dmg);
-            println!("{}", msg);
-        }
+        if hit == Hit::Block { dmg = self.modifiers.block_reduction(dmg); }
+        self.stats.record_eviscerate_dmg_and_hit(dmg, &hit,
+                                                 self.ability_costs.eviscerate);
+        self.log.log(self.timekeep.now(), CombatEvent::Ability {
+            name: "Eviscerate", hit, dmg });
     }
 
     fn slice_and_dice(&mut self) {
@@ -301,94 +394,165 @@ impl Simulator {
 
         self.enable_slice_and_dice();
         dur *= self.modifiers.general.slice_and_dice_duration_modifier;
-        self.timekeep.timers.slice_and_dice = dur;
+        self.timekeep.start_slice_and_dice_timer(dur);
         self.start_global_cd();
         self.subtract_energy(self.ability_costs.slice_and_dice);
+        self.stats.record_slice_and_dice(self.ability_costs.slice_and_dice);
         self.clear_combo_points_and_roll_for_finisher_procs();
-        self.print_slice_and_dice();
+        self.log.log(self.timekeep.now(), CombatEvent::BuffGained {
+            name: SLICE_AND_DICE, duration: dur });
     }
 
     fn enable_slice_and_dice(&mut self) {
         if self.active_buffs.slice_and_dice { return; }
-        self.modifiers.general.attack_speed_modifier *= 1.3;
+        self.change_attack_speed(1.3);
         self.active_buffs.slice_and_dice = true;
+        self.active_buffs.slice_and_dice_since = self.timekeep.now();
     }
 
     fn disable_slice_and_dice(&mut self) {
         if ! self.active_buffs.slice_and_dice { return; }
-        self.modifiers.general.attack_speed_modifier /= 1.3;
+        self.change_attack_speed(1.0 / 1.3);
         self.active_buffs.slice_and_dice = false;
-        self.print_slice_and_dice_wearing_off();
-    }
-
-    fn print_extra_combo_point_from_finisher(&self) {
-        if self.verb > 0 && ! self.stat_weights {
-            println!("Got extra combo point from finisher!");
-        }
-    }
-
-    fn print_extra_energy_from_finisher(&self) {
-        if self.verb > 0 && ! self.stat_weights {
-            println!("Got 25 energy from finisher!");
-        }
+        let uptime = self.timekeep.now()
+            - self.active_buffs.slice_and_dice_since;
+        self.stats.record_buff_uptime(SLICE_AND_DICE, uptime);
+        self.log.log(self.timekeep.now(), CombatEvent::BuffFaded {
+            name: SLICE_AND_DICE });
     }
 
     fn clear_combo_points_and_roll_for_finisher_procs(&mut self) {
-        if self.modifiers.finisher.gets_extra_combo_point() {
+        if self.modifiers.finisher.gets_extra_combo_point(&mut self.rng) {
             self.combo_points = 1;
-            self.print_extra_combo_point_from_finisher();
+            self.log.log(self.timekeep.now(), CombatEvent::ComboPointsChanged {
+                combo_points: 1, source: RUTHLESSNESS });
         } else { self.combo_points = 0; }
 
-        if self.modifiers.finisher.gets_extra_energy(self.combo_points) {
-            self.add_energy(25);
-            self.print_extra_energy_from_finisher();
+        if self.modifiers.finisher.gets_extra_energy(&mut self.rng,
+                                                      self.combo_points) {
+            self.add_energy(25, RELENTLESS_STRIKES);
         }
     }
 
-    fn add_combo_point(&mut self) {
+    fn add_combo_point(&mut self, source: &str) {
+        if self.combo_points == 5 { self.stats.record_combo_point_wasted(); }
         self.combo_points = min_i32(5, self.combo_points + 1);
+        self.log.log(self.timekeep.now(), CombatEvent::ComboPointsChanged {
+            combo_points: self.combo_points, source });
     }
 
-    fn extra_attack_procc(&mut self) {
+    fn extra_attack_procc(&mut self, name: &str) {
         self.reset_mh_swing();
-        self.add_extra_attack();
+        self.add_extra_attack(name);
     }
 
     fn reset_mh_swing(&mut self) {
-        self.timekeep.reset_mh_swing_timer(
+        let swing_time = self.timekeep.reset_mh_swing_timer(
             self.modifiers.general.attack_speed_modifier
             );
+        self.log.log(self.timekeep.now(), CombatEvent::SwingTimerReset {
+            hand: "MH", swing_time });
     }
 
     fn reset_oh_swing(&mut self) {
-        self.timekeep.reset_oh_swing_timer(
+        let swing_time = self.timekeep.reset_oh_swing_timer(
             self.modifiers.general.attack_speed_modifier
             );
+        self.log.log(self.timekeep.now(), CombatEvent::SwingTimerReset {
+            hand: "OH", swing_time });
     }
 
-    fn add_extra_attack(&mut self) {
-        self.extra_attacks += 1;
+    fn add_extra_attack(&mut self, name: &str) {
+        self.extra_attacks.push(name.to_string());
     }
 
-    fn roll_for_procc(&mut self, hit_procc: &HitProcc) {
-        let die = roll_die();
+    fn stat_buff_procc(&mut self, name: &str, strength: i32, duration: f32,
+                       slot: WeaponSlot) {
+        // the same procc on the other hand is a separate buff that stacks,
+        // while a re-procc on the same hand only refreshes the duration
+        let nr = self.active_buffs.get_or_add_stat_buff(name, strength, slot);
+        if ! self.active_buffs.stat_buffs[nr].is_active {
+            self.enable_stat_buff_by_nr(nr);
+        }
+        let expires_at = self.timekeep.now() + duration;
+        self.active_buffs.stat_buffs[nr].expires_at = expires_at;
+        self.timekeep.schedule(expires_at, EventKind::StatBuffExpiry(nr));
+        let buff_name = self.active_buffs.stat_buffs[nr].get_uptime_name();
+        self.log.log(self.timekeep.now(), CombatEvent::BuffGained {
+            name: &buff_name, duration });
+    }
+
+    fn enable_stat_buff_by_nr(&mut self, nr: usize) {
+        self.char_state.add_strength(self.active_buffs.stat_buffs[nr].strength);
+        self.recalculate_stats();
+        self.active_buffs.stat_buffs[nr].is_active = true;
+        self.active_buffs.stat_buffs[nr].started_at = self.timekeep.now();
+    }
+
+    fn disable_stat_buff_by_nr(&mut self, nr: usize) {
+        self.char_state.add_strength(
+            -self.active_buffs.stat_buffs[nr].strength);
+        self.recalculate_stats();
+        self.active_buffs.stat_buffs[nr].is_active = false;
+        let uptime = self.timekeep.now()
+            - self.active_buffs.stat_buffs[nr].started_at;
+        self.stats.record_buff_uptime(
+            &self.active_buffs.stat_buffs[nr].get_uptime_name(), uptime);
+    }
+
+    fn stat_buff_wearing_off_by_nr(&mut self, nr: usize) {
+        if ! self.active_buffs.stat_buffs[nr].is_active { return; }
+        // refreshed since this expiry was scheduled
+        if self.active_buffs.stat_buffs[nr].expires_at > self.timekeep.now() {
+            return;
+        }
+        self.disable_stat_buff_by_nr(nr);
+        let buff_name = self.active_buffs.stat_buffs[nr].get_uptime_name();
+        self.log.log(self.timekeep.now(), CombatEvent::BuffFaded {
+            name: &buff_name });
+    }
+
+    fn end_all_buffs(&mut self) {
+        // closes the uptime of everything still active when the fight ends
+        self.disable_all_stat_buffs();
+        if self.active_buffs.slice_and_dice {
+            self.disable_slice_and_dice();
+        }
+        for i in 0..self.cooldowns.len() {
+            if self.cooldowns[i].is_active { self.disable_cd_by_nr(i); }
+        }
+    }
+
+    fn disable_all_stat_buffs(&mut self) {
+        for i in 0..self.active_buffs.stat_buffs.len() {
+            if self.active_buffs.stat_buffs[i].is_active {
+                self.disable_stat_buff_by_nr(i);
+            }
+        }
+    }
+
+    fn roll_for_procc(&mut self, hit_procc: &HitProcc, slot: WeaponSlot) {
+        let die = roll_die(&mut self.rng);
         let proccs: bool;
         match hit_procc {
             HitProcc::Dmg(_,_,resist_chance,procc_chance) => {
                 if die > *procc_chance { proccs = false; }
                 else {
-                    let resist_roll = roll_die();
+                    let resist_roll = roll_die(&mut self.rng);
                     if resist_roll > *resist_chance { proccs = true; }
                     else { proccs = false; }
                 }
             },
-            HitProcc::Strength(_,_,_,procc_chance) => {
-                if die < *procc_chance { proccs = true; }
+            HitProcc::Strength(name,strength,duration,procc_chance) => {
+                if die < *procc_chance {
+                    self.stat_buff_procc(name, *strength, *duration, slot);
+                    proccs = true;
+                }
                 else { proccs = false; }
             },
-            HitProcc::ExtraAttack(_,procc_chance) => {
+            HitProcc::ExtraAttack(name,procc_chance) => {
                 if die < *procc_chance {
-                    self.extra_attack_procc();
+                    self.extra_attack_procc(name);
                     proccs = true;
                 }
                 else { proccs = false; }
@@ -396,56 +560,54 @@ impl Simulator {
             HitProcc::None => panic!("'None' proccs not allowed in simulation.")
         };
         if proccs {
-            self.print_procc(hit_procc);
+            self.log_procc(hit_procc);
             self.stats.record_procc(hit_procc);
         }
     }
 
-    fn print_procc(&mut self, procc: &HitProcc) {
-        if self.verb > 0 && ! self.stat_weights {
-            let sub_msg = match procc {
-                HitProcc::Dmg(name,dmg,_,_) =>
-                    format!("{} procc for {:.0} dmg!", name, dmg),
-                HitProcc::Strength(name,_,_,_) =>
-                    format!("Strength procc from {}!", name),
-                HitProcc::ExtraAttack(name,_) =>
-                    format!("Extra swing procc from {}!", name),
-                HitProcc::None =>
-                    panic!("'None' proccs not allowed in simulation.")
-            };
-            let msg = format!("{:.1}: {}", self.timekeep.timers.time_left,
-                              sub_msg);
-            println!("{}", msg);
-        }
+    fn log_procc(&mut self, procc: &HitProcc) {
+        let event = match procc {
+            HitProcc::Dmg(name,dmg,_,_) => CombatEvent::Procc {
+                name, effect: ProccEffect::Damage, dmg: *dmg },
+            HitProcc::Strength(name,_,_,_) => CombatEvent::Procc {
+                name, effect: ProccEffect::Strength, dmg: 0.0 },
+            HitProcc::ExtraAttack(name,_) => CombatEvent::Procc {
+                name, effect: ProccEffect::ExtraAttack, dmg: 0.0 },
+            HitProcc::None =>
+                panic!("'None' proccs not allowed in simulation.")
+        };
+        self.log.log(self.timekeep.now(), event);
     }
 
 
     fn trigger_hit_procc_mh(&mut self) {
         for i in 0..self.mh.hit_proccs.len() {
             let procc = self.mh.hit_proccs[i].clone();
-            self.roll_for_procc(&procc);
+            self.roll_for_procc(&procc, WeaponSlot::Mh);
         }
     }
 
     fn trigger_hit_procc_oh(&mut self) {
         for i in 0..self.oh.hit_proccs.len() {
             let procc = self.oh.hit_proccs[i].clone();
-            self.roll_for_procc(&procc);
+            self.roll_for_procc(&procc, WeaponSlot::Oh);
         }
     }
 
     fn backstab(&mut self) {
-        let hit: Hit = self.mh.hit_table_backstab.roll_for_hit();
+        let hit: Hit = self.mh.hit_table_backstab.roll_for_hit(&mut self.rng);
         let mut dmg = 0.0;
-        if hit == Hit::Miss || hit == Hit::Dodge {
-            let energy_cost = (0.2 * self.ability_costs.backstab as f32) as i32;
+        let mut energy_cost = 0;
+        if hit == Hit::Miss || hit == Hit::Dodge || hit == Hit::Parry {
+            energy_cost = (0.2 * self.ability_costs.backstab as f32) as i32;
             self.subtract_energy(energy_cost);
         }
-        if hit == Hit::Hit || hit == Hit::Crit {
+        if hit == Hit::Hit || hit == Hit::Crit || hit == Hit::Block {
             self.trigger_hit_procc_mh();
-            self.subtract_energy(self.ability_costs.backstab);
-            self.add_combo_point();
-            dmg = 1.5 * self.mh.mean_yellow_dmg + 210.0;
+            energy_cost = self.ability_costs.backstab;
+            self.subtract_energy(energy_cost);
+            self.add_combo_point("Backstab");
+            dmg = 1.5 * self.mh.roll_yellow_dmg(&mut self.rng) + 210.0;
             dmg *= self.modifiers.hit.backstab;
 
             if hit == Hit::Crit {
@@ -453,19 +615,16 @@ impl Simulator {
             }
         }
         dmg = self.modifiers.armor_reduction(dmg);
-        self.stats.record_backstab_dmg_and_hit(dmg, &hit);
+        if hit == Hit::Block { dmg = self.modifiers.block_reduction(dmg); }
+        self.stats.record_backstab_dmg_and_hit(dmg, &hit, energy_cost);
         self.start_global_cd();
 
-        if self.verb > 0 && ! self.stat_weights {
-            let msg = format!("{:.1}: Backstab {} for {:.0} dmg.",
-                              self.timekeep.timers.time_left, hit, dmg);
-            println!("{}", msg);
-        }
-
+        self.log.log(self.timekeep.now(), CombatEvent::Ability {
+            name: "Backstab", hit, dmg });
     }
 
     fn start_global_cd(&mut self) {
-        self.timekeep.timers.global_cd = 1.0;
+        self.timekeep.start_global_cd_timer(1.0);
     }
 
     fn backstab_evis_rotation(&mut self) {
@@ -473,7 +632,7 @@ impl Simulator {
         let can_eviscerate = self.energy >= self.ability_costs.eviscerate;
         let can_slice_and_dice =
             self.energy >= self.ability_costs.slice_and_dice;
-        let active_slice_and_dice = self.timekeep.timers.slice_and_dice > 0.0;
+        let active_slice_and_dice = self.timekeep.slice_and_dice_is_active();
 
         if self.combo_points == 2 && ! active_slice_and_dice
             && can_slice_and_dice { self.slice_and_dice() }
@@ -484,13 +643,14 @@ impl Simulator {
             && can_eviscerate { self.eviscerate(); }
     }
 
-    fn perform_mh_strike(&mut self) {
+    fn roll_mh_white_hit_and_dmg(&mut self) -> (Hit, f32) {
 
-        let hit: Hit = self.mh.hit_table_white.roll_for_hit();
+        let hit: Hit = self.mh.hit_table_white.roll_for_hit(&mut self.rng);
         let mut dmg = 0.0;
-        if hit == Hit::Hit || hit == Hit::Crit || hit == Hit::Glancing {
+        if hit == Hit::Hit || hit == Hit::Crit || hit == Hit::Glancing
+            || hit == Hit::Block {
             self.trigger_hit_procc_mh();
-            dmg = self.mh.mean_white_dmg;
+            dmg = self.mh.roll_white_dmg(&mut self.rng);
 
             if hit == Hit::Glancing {
                 dmg *= self.modifiers.hit.glancing_mh;
@@ -499,33 +659,32 @@ impl Simulator {
             }
         }
         dmg = self.modifiers.armor_reduction(dmg);
-        self.stats.record_mh_white_dmg_and_hit(dmg, &hit);
-        self.print_mh_hit_and_dmg(hit, dmg);
+        if hit == Hit::Block { dmg = self.modifiers.block_reduction(dmg); }
+        return (hit, dmg);
     }
 
-    fn print_mh_hit_and_dmg(&mut self, hit: Hit, dmg: f32) {
-        if self.verb > 0 && ! self.stat_weights {
-            let msg = format!("{:.1}: MH {} for {:.0} dmg.",
-                              self.timekeep.timers.time_left, hit, dmg);
-            println!("{}", msg);
-        }
+    fn perform_mh_strike(&mut self) {
+        let (hit, dmg) = self.roll_mh_white_hit_and_dmg();
+        self.stats.record_mh_white_dmg_and_hit(dmg, &hit);
+        self.log.log(self.timekeep.now(), CombatEvent::Swing {
+            hand: "MH", extra_attack: None, hit, dmg });
     }
 
-    fn print_oh_hit_and_dmg(&mut self, hit: Hit, dmg: f32) {
-        if self.verb > 0 && ! self.stat_weights {
-            let msg = format!("{:.1}: OH {} for {:.0} dmg.",
-                              self.timekeep.timers.time_left, hit, dmg);
-            println!("{}", msg);
-        }
+    fn perform_extra_attack(&mut self, name: &str) {
+        let (hit, dmg) = self.roll_mh_white_hit_and_dmg();
+        self.stats.record_extra_attack_dmg_and_hit(name, dmg, &hit);
+        self.log.log(self.timekeep.now(), CombatEvent::Swing {
+            hand: "MH", extra_attack: Some(name), hit, dmg });
     }
 
     fn perform_oh_strike(&mut self) {
 
-        let hit: Hit = self.oh.hit_table_white.roll_for_hit();
+        let hit: Hit = self.oh.hit_table_white.roll_for_hit(&mut self.rng);
         let mut dmg = 0.0;
-        if hit == Hit::Hit || hit == Hit::Crit || hit == Hit::Glancing {
+        if hit == Hit::Hit || hit == Hit::Crit || hit == Hit::Glancing
+            || hit == Hit::Block {
             self.trigger_hit_procc_oh();
-            dmg = self.oh.mean_white_dmg;
+            dmg = self.oh.roll_white_dmg(&mut self.rng);
             dmg *= self.modifiers.hit.oh;
 
             if hit == Hit::Glancing {
@@ -535,20 +694,22 @@ impl Simulator {
             }
         }
         dmg = self.modifiers.armor_reduction(dmg);
+        if hit == Hit::Block { dmg = self.modifiers.block_reduction(dmg); }
         self.stats.record_oh_white_dmg_and_hit(dmg, &hit);
-        self.print_oh_hit_and_dmg(hit, dmg);
+        self.log.log(self.timekeep.now(), CombatEvent::Swing {
+            hand: "OH", extra_attack: None, hit, dmg });
     }
 
-    fn check_mh_swing_timer_and_strike(&mut self) {
-        if self.timekeep.timers.mh_swing > 0.0 { return; }
+    fn mh_swing(&mut self) {
         self.perform_mh_strike();
         self.reset_mh_swing();
+        self.do_extra_attacks();
     }
 
-    fn check_oh_swing_timer_and_strike(&mut self) {
-        if self.timekeep.timers.oh_swing > 0.0 { return; }
+    fn oh_swing(&mut self) {
         self.perform_oh_strike();
         self.reset_oh_swing();
+        self.do_extra_attacks();
     }
 
     pub fn print_stats(&mut self) {
@@ -557,14 +718,16 @@ impl Simulator {
 
     fn cd_by_nr_lacks_prerequisite(&mut self, nr: usize) -> bool {
         let mut lacks_req = false;
-        if self.cooldowns[nr].cd_left > 0.0 { lacks_req = true; }
+        if self.cooldowns[nr].ready_at > self.timekeep.now() {
+            lacks_req = true;
+        }
         else if self.cooldowns[nr].is_active { lacks_req = true; }
         else if self.cooldowns[nr].cost > self.energy { lacks_req = true; }
         else if self.energy > self.cooldowns[nr].use_below_energy {
             lacks_req = true;
         }
         else if self.cooldowns[nr].cost > 0
-            && self.timekeep.timers.global_cd > 0.0 {
+            && self.timekeep.global_cd_is_active() {
                 lacks_req = true;
             }
         return lacks_req;
@@ -572,31 +735,40 @@ impl Simulator {
 
     fn enable_cd_by_nr(&mut self, nr: usize) {
         self.subtract_energy(self.cooldowns[nr].cost);
+        self.stats.record_cooldown_activation(&self.cooldowns[nr].name);
         if self.cooldowns[nr].cost > 0 { self.start_global_cd(); }
         match self.cooldowns[nr].effect {
             EnergyRegenMultiplier(mult, duration) => {
                 self.modifiers.general.energy_regen_modifier *= mult;
-                self.cooldowns[nr].is_active = true;
-                self.cooldowns[nr].cd_left = self.cooldowns[nr].cd;
-                self.cooldowns[nr].time_left = duration;
+                self.activate_cd_by_nr(nr, duration);
             },
             AttackSpeedMultiplier(mult, duration) => {
-                self.modifiers.general.attack_speed_modifier *= mult;
-                self.cooldowns[nr].is_active = true;
-                self.cooldowns[nr].cd_left = self.cooldowns[nr].cd;
-                self.cooldowns[nr].time_left = duration;
+                self.change_attack_speed(mult);
+                self.activate_cd_by_nr(nr, duration);
             },
             InstantEnergyRefill(energy) => {
-                self.add_energy(energy);
-                self.cooldowns[nr].cd_left = self.cooldowns[nr].cd;
+                let name = self.cooldowns[nr].name.clone();
+                self.add_energy(energy, &name);
             }
         }
+        let ready_at = self.timekeep.now() + self.cooldowns[nr].cd;
+        self.cooldowns[nr].ready_at = ready_at;
+        self.timekeep.schedule(ready_at, EventKind::CooldownReady(nr));
+    }
+
+    fn activate_cd_by_nr(&mut self, nr: usize, duration: f32) {
+        let active_until = self.timekeep.now() + duration;
+        self.cooldowns[nr].is_active = true;
+        self.cooldowns[nr].active_since = self.timekeep.now();
+        self.cooldowns[nr].active_until = active_until;
+        self.timekeep.schedule(active_until, EventKind::CooldownExpiry(nr));
     }
 
     fn use_cd_by_nr(&mut self, nr: usize) {
         if self.cd_by_nr_lacks_prerequisite(nr) { return; }
         self.enable_cd_by_nr(nr);
-        if self.verb > 0 && ! self.stat_weights { self.print_cd_usage_by_nr(nr); }
+        self.log.log(self.timekeep.now(), CombatEvent::CooldownUsed {
+            name: &self.cooldowns[nr].name });
     }
 
     fn use_ready_cooldowns(&mut self) {
@@ -605,27 +777,13 @@ impl Simulator {
         }
     }
 
-    fn print_cd_usage_by_nr(&self, nr: usize) {
-        let sub_msg = match self.cooldowns[nr].effect {
-            EnergyRegenMultiplier(_,_) => {
-                format!(", total energy regen multiplier is {}!",
-                        self.modifiers.general.energy_regen_modifier)
-            },
-            AttackSpeedMultiplier(_,_) => {
-                format!(", total attack speed multiplier is {:.3}!",
-                        self.modifiers.general.attack_speed_modifier)
-            },
-            InstantEnergyRefill(energy) => {
-                format!(", gaining {} energy!", energy)
-            }
-        };
-        let msg = format!("{:.1}: Used {}{}", self.timekeep.timers.time_left,
-                          self.cooldowns[nr].name, sub_msg);
-        println!("{}", msg);
-    }
-
     fn reset_char(&mut self) {
         self.energy = self.modifiers.general.energy_max;
+        self.energy_since = 0.0;
+        self.combo_points = 0;
+        self.extra_attacks.clear();
+        self.char_state.reset();
+        self.recalculate_stats();
         self.reset_cooldowns();
         if self.active_buffs.slice_and_dice {
             self.disable_slice_and_dice();
@@ -638,56 +796,60 @@ impl Simulator {
         }
     }
 
-    pub fn simulate(&mut self) {
+    fn seed_rng_for_iteration(&mut self, iteration: u64) {
+        // every iteration gets its own stream, so a run gives the same
+        // result no matter how the iterations are spread over threads
+        let mut seed = [0u8; 32];
+        seed[..8].copy_from_slice(&self.seed.to_le_bytes());
+        seed[8..16].copy_from_slice(&iteration.to_le_bytes());
+        self.rng = StdRng::from_seed(seed);
+    }
+
+    pub fn simulate(&mut self, iteration: u64) {
+        self.seed_rng_for_iteration(iteration);
+        self.log.start_iteration(iteration);
         self.stats.clear();
         self.timekeep.reset_timers();
         self.reset_char();
 
-        while self.timekeep.timers.time_left > 0.0 {
+        loop {
             self.use_ready_cooldowns();
             self.perform_apt_yellow_ability();
-            self.check_oh_swing_timer_and_strike();
-            self.check_mh_swing_timer_and_strike();
+            // extra attacks proccing from yellow hits swing right away
             self.do_extra_attacks();
 
-            self.check_energy_timer_and_refill_energy();
-
-            self.take_time_step_and_remove_buffs();
-
+            let event = self.timekeep.next_event();
+            self.stats.set_time(self.timekeep.now());
+            if event == EventKind::FightEnd { break; }
+            self.handle_event(event);
         }
+        self.end_all_buffs();
+        self.record_energy_level();
         self.print_at_end_of_simulation();
     }
 
-    fn do_extra_attacks(&mut self) {
-        while self.extra_attacks > 0 {
-            self.perform_mh_strike();
-            self.extra_attacks -= 1;
-        }
-    }
-
-    fn set_max_time_step_possible(&mut self) {
-        self.timekeep.set_time_step();
-    }
-
-    fn take_time_step_and_remove_buffs(&mut self) {
-        self.set_max_time_step_possible();
-        self.timekeep.take_time_step();
-        self.check_cds_wearing_off();
-        self.check_slice_and_dice_wearing_off();
-    }
-
-    fn check_slice_and_dice_wearing_off(&mut self) {
-        let is_active = self.active_buffs.slice_and_dice;
-        let should_not_be_active = self.timekeep.timers.slice_and_dice < 0.0;
-        if is_active && should_not_be_active {
-            self.disable_slice_and_dice();
+    fn handle_event(&mut self, event: EventKind) {
+        match event {
+            EventKind::MhSwing => self.mh_swing(),
+            EventKind::OhSwing => self.oh_swing(),
+            EventKind::EnergyRefill => self.energy_tick(),
+            EventKind::SliceAndDiceExpiry => self.disable_slice_and_dice(),
+            EventKind::CooldownExpiry(nr) => self.cd_wearing_off_by_nr(nr),
+            EventKind::StatBuffExpiry(nr) =>
+                self.stat_buff_wearing_off_by_nr(nr),
+            // the events below only give the rotation a chance to act
+            EventKind::GlobalCdEnd => (),
+            EventKind::CooldownReady(_) => (),
+            EventKind::FightEnd => ()
         }
     }
 
-    fn print_slice_and_dice_wearing_off(&mut self) {
-        if self.verb > 1 && ! self.stat_weights {
-            println!("{:.1}: Slice and dice wore off.",
-                     self.timekeep.timers.time_left);
+    fn do_extra_attacks(&mut self) {
+        // extra attacks may procc further extra attacks, which are queued
+        // up and performed in the same loop
+        while ! self.extra_attacks.is_empty() {
+            let name = self.extra_attacks.remove(0);
+            self.perform_extra_attack(&name);
         }
     }
 
@@ -699,59 +861,41 @@ impl Simulator {
                 if is_active {
                     self.modifiers.general.energy_regen_modifier /= mult;
                 }
-                self.cooldowns[nr].cd_left = 0.0;
-                self.cooldowns[nr].time_left = 0.0;
             },
             AttackSpeedMultiplier(mult,_) => {
                 if is_active {
-                    self.modifiers.general.attack_speed_modifier /= mult;
+                    self.change_attack_speed(1.0 / mult);
                 }
-                self.cooldowns[nr].cd_left = 0.0;
-                self.cooldowns[nr].time_left = 0.0;
             },
-            InstantEnergyRefill(_) => {
-                self.cooldowns[nr].cd_left = 0.0;
-            }
+            InstantEnergyRefill(_) => ()
         }
+        self.cooldowns[nr].ready_at = 0.0;
+        self.cooldowns[nr].active_until = 0.0;
     }
 
     fn disable_cd_by_nr(&mut self, nr: usize) {
         self.cooldowns[nr].is_active = false;
+        let uptime = self.timekeep.now() - self.cooldowns[nr].active_since;
+        self.stats.record_buff_uptime(&self.cooldowns[nr].name, uptime);
         match self.cooldowns[nr].effect {
             EnergyRegenMultiplier(mult,_) => {
                 self.modifiers.general.energy_regen_modifier /= mult;
             }
             AttackSpeedMultiplier(mult,_) => {
-                self.modifiers.general.attack_speed_modifier /= mult;
+                self.change_attack_speed(1.0 / mult);
             }
             InstantEnergyRefill(_) => (),
         }
     }
 
-    fn check_cds_wearing_off(&mut self) {
-        for i in 0..self.cooldowns.len() {
-            if self.cooldowns[i].cd_left > 0.0 {
-                self.cooldowns[i].cd_left -= self.timekeep.dt;
-            }
-            if self.cooldowns[i].time_left > 0.0 {
-                self.cooldowns[i].time_left -= self.timekeep.dt;
-            }
-            if self.cooldowns[i].time_left <= 0.0 &&
-                self.cooldowns[i].is_active {
-                    self.disable_cd_by_nr(i);
-                    self.print_cd_wearing_off_by_nr(i);
-                }
-        }
-    }
-
-
-    fn print_cd_wearing_off_by_nr(&mut self, nr: usize) {
-        let name = &self.cooldowns[nr].name;
-        let cd = self.cooldowns[nr].cd;
-        if self.verb > 1 && ! self.stat_weights {
-            println!("{:.1}: {} wore off, {}s cooldown.",
-                     self.timekeep.timers.time_left, name, cd);
-        }
+    fn cd_wearing_off_by_nr(&mut self, nr: usize) {
+        if ! self.cooldowns[nr].is_active { return; }
+        // the cooldown might have been reset and used again since this
+        // expiry was scheduled
+        if self.cooldowns[nr].active_until > self.timekeep.now() { return; }
+        self.disable_cd_by_nr(nr);
+        self.log.log(self.timekeep.now(), CombatEvent::CooldownFaded {
+            name: &self.cooldowns[nr].name, cooldown: self.cooldowns[nr].cd });
     }
 
     fn print_at_end_of_simulation(&mut self) {
@@ -763,43 +907,116 @@ impl Simulator {
         }
     }
 
-    fn check_energy_timer_and_refill_energy(&mut self) {
-
-        if self.timekeep.timers.energy_refill <= 0.0 {
-            self.timekeep.reset_energy_timer();
-            self.refill_energy();
-        }
+    fn energy_tick(&mut self) {
+        self.timekeep.reset_energy_timer();
+        self.refill_energy();
     }
 
     fn refill_energy(&mut self) {
         let mut refill: i32;
-        let die = roll_die();
+        let die = roll_die(&mut self.rng);
         if die < 0.25 { refill = 21; }
         else { refill = 20; }
         refill *= self.modifiers.general.energy_regen_modifier;
-        self.add_energy(refill);
-        if self.verb > 1 && ! self.stat_weights { self.show_energy_refill(); }
-    }
-
-    fn show_energy_refill(&mut self) {
-        let msg = format!("{:.1}: Energy refilled to {}.",
-                          self.timekeep.timers.time_left,
-                          self.energy);
-        println!("{}", msg);
+        self.add_energy(refill, ENERGY_TICKS);
     }
 }
 
 #[derive(Debug)]
 struct ActiveBuffs {
-    slice_and_dice: bool
+    slice_and_dice: bool,
+    slice_and_dice_since: f32,
+    stat_buffs: Vec<StatBuff>
 }
 
 impl ActiveBuffs {
     fn new() -> ActiveBuffs {
         ActiveBuffs {
-            slice_and_dice: false
+            slice_and_dice: false,
+            slice_and_dice_since: 0.0,
+            stat_buffs: Vec::new()
+        }
+    }
+
+    fn get_or_add_stat_buff(&mut self, name: &str, strength: i32,
+                            slot: WeaponSlot) -> usize {
+        for i in 0..self.stat_buffs.len() {
+            if self.stat_buffs[i].name == name
+                && self.stat_buffs[i].weapon_slot == slot {
+                return i;
+            }
+        }
+        self.stat_buffs.push(StatBuff::new(name, strength, slot));
+        return self.stat_buffs.len() - 1;
+    }
+}
+
+#[derive(Debug)]
+struct StatBuff {
+    name: String,
+    strength: i32,
+    weapon_slot: WeaponSlot,
+    is_active: bool,
+    started_at: f32,
+    expires_at: f32
+}
+
+impl StatBuff {
+    fn new(name: &str, strength: i32, slot: WeaponSlot) -> StatBuff {
+        StatBuff {
+            name: name.to_string(),
+            strength,
+            weapon_slot: slot,
+            is_active: false,
+            started_at: 0.0,
+            expires_at: 0.0
+        }
+    }
+
+    fn get_uptime_name(&self) -> String {
+        return get_stat_buff_uptime_name(&self.name, self.weapon_slot);
+    }
+}
+
+pub fn get_stat_buff_uptime_name(name: &str, slot: WeaponSlot) -> String {
+    return format!("{} ({})", name, slot);
+}
+
+#[derive(Debug)]
+struct CharacterState {
+    // stats of the character as configured, and the live stats that also
+    // include all temporary effects currently active
+    base_prim_stats: PrimStats,
+    base_sec_stats: SecStats,
+    prim_stats: PrimStats,
+    sec_stats: SecStats
+}
+
+impl CharacterState {
+    fn new() -> CharacterState {
+        CharacterState {
+            base_prim_stats: PrimStats::new_from_race(Race::None),
+            base_sec_stats: SecStats::new_from_race(Race::None),
+            prim_stats: PrimStats::new_from_race(Race::None),
+            sec_stats: SecStats::new_from_race(Race::None)
         }
     }
+
+    fn set_from_character(&mut self, character: &Character) {
+        self.base_prim_stats = character.prim_stats;
+        self.base_sec_stats = character.sec_stats;
+        self.reset();
+    }
+
+    fn reset(&mut self) {
+        self.prim_stats = self.base_prim_stats;
+        self.sec_stats = self.base_sec_stats;
+    }
+
+    fn add_strength(&mut self, strength: i32) {
+        self.prim_stats.strength += strength;
+        self.sec_stats.attack_power += strength;
+    }
 }
 
 #[derive(Debug)]
@@ -825,169 +1042,231 @@ impl AbilityCosts {
 
 #[derive(Debug)]
 struct Timers {
+    // all timers are absolute points in time counted from the start of the
+    // fight, at which the corresponding event happens
+    now: f32,
     energy_refill: f32,
     slice_and_dice: f32,
-    time_left: f32,
     global_cd: f32,
     mh_swing: f32,
-    oh_swing: f32,
-    glob_cd_previously_available: bool
+    oh_swing: f32
 }
 
 impl Timers {
     fn new() -> Timers {
         Timers {
+            now: 0.0,
             energy_refill: 1.0,
             slice_and_dice: 0.0,
-            time_left: 0.0,
             global_cd: 0.0,
             mh_swing: 0.0,
-            oh_swing: 0.0,
-            glob_cd_previously_available: true
-        }
-    }
-
-    fn _debug_dynamic_step(&self, step_size: f32) {
-        println!("energy refill: {}\n\
-                 global cd: {}\n\
-                 mh swing: {}\n\
-                 oh swing: {}\n\
-                 global cd available last time step: {}",
-                 self.energy_refill,
-                 self.global_cd,
-                 self.mh_swing,
-                 self.oh_swing,
-                 self.glob_cd_previously_available
-                 );
-        println!("taking step: {}\n", step_size);
-    }
-
-    fn get_max_time_step(&mut self) -> f32 {
-        let mut max_time_step = 10.0;
-        max_time_step = min_f32(max_time_step, self.energy_refill);
-        max_time_step = min_f32(max_time_step, self.mh_swing);
-        max_time_step = min_f32(max_time_step, self.oh_swing);
-        if ! self.glob_cd_previously_available {
-            max_time_step = min_f32(max_time_step, self.global_cd);
-        }
-        max_time_step = max_f32(max_time_step, 0.01);
-        if self.global_cd <= 0.0 {
-            self.glob_cd_previously_available = true;
-        } else {
-            self.glob_cd_previously_available = false;
+            oh_swing: 0.0
         }
-        // self._debug_dynamic_step(max_time_step);
-        return max_time_step;
     }
 
-    fn reset_with_fight_length(&mut self, fight_length: f32) {
+    fn reset(&mut self) {
+        self.now = 0.0;
         self.energy_refill = 1.0;
         self.slice_and_dice = 0.0;
-        self.time_left = fight_length;
         self.global_cd = 0.0;
-        self.glob_cd_previously_available = true;
         self.mh_swing = 0.0;
         self.oh_swing = 0.0;
     }
 
 }
 
+#[derive(Clone,Copy,Debug,PartialEq)]
+enum EventKind {
+    MhSwing,
+    OhSwing,
+    EnergyRefill,
+    GlobalCdEnd,
+    SliceAndDiceExpiry,
+    CooldownExpiry(usize), // index into the cooldown vector
+    CooldownReady(usize), // index into the cooldown vector
+    StatBuffExpiry(usize), // index into the stat buff vector
+    FightEnd
+}
+
+#[derive(Debug)]
+struct Event {
+    time: f32,
+    // order in which the event was scheduled, breaks ties between events
+    // happening at the same time
+    seq: u64,
+    kind: EventKind
+}
+
+impl PartialEq for Event {
+    fn eq(&self, other: &Event) -> bool {
+        return self.cmp(other) == Ordering::Equal;
+    }
+}
+
+impl Eq for Event {}
+
+impl PartialOrd for Event {
+    fn partial_cmp(&self, other: &Event) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl Ord for Event {
+    fn cmp(&self, other: &Event) -> Ordering {
+        // reversed, so that the max-heap pops the earliest event first
+        return other.time.total_cmp(&self.time)
+            .then_with(|| other.seq.cmp(&self.seq));
+    }
+}
 
 #[derive(Debug)]
 struct TimeKeeper {
     timers: Timers,
+    queue: BinaryHeap<Event>,
+    n_scheduled: u64,
     fight_length: f32,
-    dt: f32,
     mh_swing_interval: f32,
-    oh_swing_interval: f32,
-    verb: i32,
-    stat_weights: bool
+    oh_swing_interval: f32
 }
 
 impl TimeKeeper {
     fn new() -> TimeKeeper {
         TimeKeeper {
             timers: Timers::new(),
+            queue: BinaryHeap::new(),
+            n_scheduled: 0,
             fight_length: 0.0,
-            dt: 0.0,
             mh_swing_interval: 0.0,
-            oh_swing_interval: 0.0,
-            verb: 0,
-            stat_weights: false
+            oh_swing_interval: 0.0
         }
     }
 
-    fn set_time_step(&mut self) {
-        self.dt = self.timers.get_max_time_step();
+    fn now(&self) -> f32 { return self.timers.now; }
+
+    fn schedule(&mut self, time: f32, kind: EventKind) {
+        self.queue.push(Event { time, seq: self.n_scheduled, kind });
+        self.n_scheduled += 1;
     }
 
-    fn set_mh_swing_interval(&mut self, weapon: &Weapon) {
-        self.mh_swing_interval = weapon.get_swing_interval();
+    fn next_event(&mut self) -> EventKind {
+        // events whose timer has been moved since they were scheduled are
+        // outdated and silently dropped
+        loop {
+            let event = self.queue.pop()
+                .expect("Event queue ran dry before the end of the fight.");
+            if self.is_outdated(&event) { continue; }
+            self.timers.now = event.time;
+            return event.kind;
+        }
     }
 
-    fn set_oh_swing_interval(&mut self, weapon: &Weapon) {
-        self.oh_swing_interval = weapon.get_swing_interval();
+    fn is_outdated(&self, event: &Event) -> bool {
+        let current_time = match event.kind {
+            EventKind::MhSwing => self.timers.mh_swing,
+            EventKind::OhSwing => self.timers.oh_swing,
+            EventKind::EnergyRefill => self.timers.energy_refill,
+            EventKind::GlobalCdEnd => self.timers.global_cd,
+            EventKind::SliceAndDiceExpiry => self.timers.slice_and_dice,
+            // cooldowns are kept track of by the simulator
+            EventKind::CooldownExpiry(_) => return false,
+            EventKind::CooldownReady(_) => return false,
+            EventKind::StatBuffExpiry(_) => return false,
+            EventKind::FightEnd => return false
+        };
+        return event.time != current_time;
     }
 
-    fn reset_mh_swing_timer(&mut self, factor: f32) {
-        self.timers.mh_swing = self.mh_swing_interval / factor;
-        if self.verb > 1 && ! self.stat_weights {
-            let msg = format!("{:.1}: Reset MH swing timer to {:.2}s.",
-                              self.timers.time_left, self.timers.mh_swing);
-            println!("{}", msg);
-        }
+    fn global_cd_is_active(&self) -> bool {
+        return self.timers.global_cd > self.timers.now;
     }
 
-    fn reset_oh_swing_timer(&mut self, factor: f32) {
-        self.timers.oh_swing = self.oh_swing_interval / factor;
-        if self.verb > 1 && ! self.stat_weights {
-            let msg = format!("{:.1}: Reset OH swing timer to {:.2}s.",
-                              self.timers.time_left, self.timers.oh_swing);
-            println!("{}", msg);
-        }
+    fn start_global_cd_timer(&mut self, duration: f32) {
+        self.timers.global_cd = self.timers.now + duration;
+        self.schedule(self.timers.global_cd, EventKind::GlobalCdEnd);
     }
 
-    fn reset_energy_timer(&mut self) { self.timers.energy_refill = 2.0; }
+    fn slice_and_dice_is_active(&self) -> bool {
+        return self.timers.slice_and_dice > self.timers.now;
+    }
 
-    fn reset_timers(&mut self) {
-        self.timers.reset_with_fight_length(self.fight_length);
+    fn start_slice_and_dice_timer(&mut self, duration: f32) {
+        self.timers.slice_and_dice = self.timers.now + duration;
+        self.schedule(self.timers.slice_and_dice,
+                      EventKind::SliceAndDiceExpiry);
     }
 
-    fn take_time_step(&mut self) {
+    fn set_mh_swing_interval(&mut self, weapon: &Weapon) {
+        self.mh_swing_interval = weapon.get_swing_interval();
+    }
 
-        if self.timers.energy_refill > 0.0 {
-            self.timers.energy_refill -= self.dt;
-        }
-        if self.timers.slice_and_dice > 0.0 {
-            self.timers.slice_and_dice -= self.dt;
-        }
-        if self.timers.time_left > 0.0 {
-            self.timers.time_left -= self.dt;
-        }
-        if self.timers.global_cd > 0.0 {
-            self.timers.global_cd -= self.dt;
-        }
-        if self.timers.mh_swing > 0.0 {
-            self.timers.mh_swing -= self.dt;
+    fn set_oh_swing_interval(&mut self, weapon: &Weapon) {
+        self.oh_swing_interval = weapon.get_swing_interval();
+    }
+
+    fn reset_mh_swing_timer(&mut self, factor: f32) -> f32 {
+        let swing_time = self.mh_swing_interval / factor;
+        self.timers.mh_swing = self.timers.now + swing_time;
+        self.schedule(self.timers.mh_swing, EventKind::MhSwing);
+        return swing_time;
+    }
+
+    fn reset_oh_swing_timer(&mut self, factor: f32) -> f32 {
+        let swing_time = self.oh_swing_interval / factor;
+        self.timers.oh_swing = self.timers.now + swing_time;
+        self.schedule(self.timers.oh_swing, EventKind::OhSwing);
+        return swing_time;
+    }
+
+    fn rescale_swing_timers(&mut self, attack_speed_mult: f32) {
+        // the part of a swing that is left is sped up or slowed down
+        let now = self.timers.now;
+        if self.timers.mh_swing > now {
+            self.timers.mh_swing = now
+                + (self.timers.mh_swing - now) / attack_speed_mult;
+            self.schedule(self.timers.mh_swing, EventKind::MhSwing);
         }
-        if self.timers.oh_swing > 0.0 {
-            self.timers.oh_swing -= self.dt;
+        if self.timers.oh_swing > now {
+            self.timers.oh_swing = now
+                + (self.timers.oh_swing - now) / attack_speed_mult;
+            self.schedule(self.timers.oh_swing, EventKind::OhSwing);
         }
+    }
+
+    fn reset_energy_timer(&mut self) {
+        self.timers.energy_refill = self.timers.now + 2.0;
+        self.schedule(self.timers.energy_refill, EventKind::EnergyRefill);
+    }
 
+    fn reset_timers(&mut self) {
+        self.timers.reset();
+        self.queue.clear();
+        self.n_scheduled = 0;
+        // the end of the fight is scheduled first so that it is handled
+        // before any other event happening at the exact same time
+        self.schedule(self.fight_length, EventKind::FightEnd);
+        self.schedule(self.timers.oh_swing, EventKind::OhSwing);
+        self.schedule(self.timers.mh_swing, EventKind::MhSwing);
+        self.schedule(self.timers.energy_refill, EventKind::EnergyRefill);
     }
 }
 
 #[derive(Debug)]
 struct WepSimulator {
     weapon_type: WeaponType,
-    mean_white_dmg: f32,
-    mean_yellow_dmg: f32,
+    min_dmg: f32,
+    max_dmg: f32,
+    white_ap_dmg: f32,
+    yellow_ap_dmg: f32,
+    swing_interval: f32,
     normalized_speed: f32,
+    extra_crit: f32,
+    extra_backstab_crit: f32,
     hit_table_yellow: YellowHitTable,
     hit_table_backstab: YellowHitTable,
     hit_table_white: WhiteHitTable,
     hit_proccs: Vec<HitProcc>,
     enemy_lvl: i32,
+    behind_target: bool,
     weapon_slot: WeaponSlot
 }
 
@@ -995,25 +1274,37 @@ impl WepSimulator {
     fn new() -> WepSimulator {
         WepSimulator {
             weapon_type: WeaponType::None,
-            mean_white_dmg: 0.0,
-            mean_yellow_dmg: 0.0,
+            min_dmg: 0.0,
+            max_dmg: 0.0,
+            white_ap_dmg: 0.0,
+            yellow_ap_dmg: 0.0,
+            swing_interval: 0.0,
             normalized_speed: 0.0,
+            extra_crit: 0.0,
+            extra_backstab_crit: 0.0,
             hit_table_yellow: YellowHitTable::new(),
             hit_table_backstab: YellowHitTable::new(),
             hit_table_white: WhiteHitTable::new(),
             hit_proccs: Vec::new(),
             enemy_lvl: 0,
+            behind_target: true,
             weapon_slot: WeaponSlot::None
         }
     }
 
     fn set_weapon_type_and_normalized_speed(&mut self, weapon: &Weapon) {
         self.weapon_type = weapon.get_weapon_type();
+        self.swing_interval = weapon.get_swing_interval();
         self.set_normalized_speed();
     }
 
     fn get_weapon_type(&self) -> WeaponType { return self.weapon_type; }
 
+    fn set_target(&mut self, target: &Target) {
+        self.enemy_lvl = target.level;
+        self.behind_target = target.behind;
+    }
+
     fn set_normalized_speed(&mut self) {
         if self.weapon_type == WeaponType::Dagger { self.normalized_speed = 1.7; }
         else if self.weapon_type == WeaponType::Sword {
@@ -1039,26 +1330,46 @@ impl WepSimulator {
     }
 
     fn set_mechanics_from_character(&mut self, character: &Character) {
-        self.set_wep_white_dmg(character);
-        self.set_wep_yellow_dmg(character);
-        self.set_hit_tables(character);
-
+        self.set_base_dmg(character);
         self.set_hit_proccs(&character);
-        self.apply_enchant_dmg(&character);
+        self.set_mechanics_from_stats(&character.prim_stats,
+                                      &character.sec_stats);
+    }
+
+    fn set_mechanics_from_stats(&mut self, prim_stats: &PrimStats,
+                                sec_stats: &SecStats) {
+        self.set_wep_white_dmg(sec_stats);
+        self.set_wep_yellow_dmg(sec_stats);
+        self.set_hit_tables(prim_stats, sec_stats);
     }
 
-    fn apply_enchant_dmg(&mut self, character: &Character) {
+    fn set_base_dmg(&mut self, character: &Character) {
+        let mut extra_dmg = 0.0;
         if self.is_main_hand() {
+            self.min_dmg = character.mh.get_min_dmg();
+            self.max_dmg = character.mh.get_max_dmg();
             for i in 0..character.mh_enchants.len() {
-                self.mean_white_dmg += character.mh_enchants[i].extra_damage;
-                self.mean_yellow_dmg += character.mh_enchants[i].extra_damage;
+                extra_dmg += character.mh_enchants[i].extra_damage;
             }
         } else if self.is_off_hand() {
+            self.min_dmg = character.oh.get_min_dmg();
+            self.max_dmg = character.oh.get_max_dmg();
             for i in 0..character.oh_enchants.len() {
-                self.mean_white_dmg += character.oh_enchants[i].extra_damage;
-                self.mean_yellow_dmg += character.oh_enchants[i].extra_damage;
+                extra_dmg += character.oh_enchants[i].extra_damage;
             }
         } else { panic!("Uninitialized weapon"); }
+        self.min_dmg += extra_dmg;
+        self.max_dmg += extra_dmg;
+    }
+
+    fn roll_white_dmg(&self, rng: &mut StdRng) -> f32 {
+        return roll_between(rng, self.min_dmg, self.max_dmg)
+            + self.white_ap_dmg;
+    }
+
+    fn roll_yellow_dmg(&self, rng: &mut StdRng) -> f32 {
+        return roll_between(rng, self.min_dmg, self.max_dmg)
+            + self.yellow_ap_dmg;
     }
 
     fn set_hit_proccs(&mut self, character: &Character) {
@@ -1094,73 +1405,62 @@ impl WepSimulator {
         }
     }
 
-    fn set_wep_white_dmg(&mut self, character: &Character) {
-
-        let swing_speed: f32;
-        let mean_dmg: f32;
-        if self.is_main_hand() {
-            mean_dmg = character.mh.get_mean_dmg();
-            swing_speed = character.mh.get_swing_interval();
-        } else {
-            mean_dmg = character.oh.get_mean_dmg();
-            swing_speed = character.oh.get_swing_interval();
-        }
-         self.mean_white_dmg = mean_dmg
-            + swing_speed * character.sec_stats.attack_power as f32 / 14.0;
+    fn set_wep_white_dmg(&mut self, sec_stats: &SecStats) {
+        self.white_ap_dmg =
+            self.swing_interval * sec_stats.attack_power as f32 / 14.0;
     }
 
-    fn set_wep_yellow_dmg(&mut self, character: &Character) {
-
-        let mean_dmg: f32;
-        if self.is_off_hand() {
-            mean_dmg = character.oh.get_mean_dmg();
-        } else {
-            mean_dmg = character.mh.get_mean_dmg();
-        }
-        let extra_portion =  self.normalized_speed
-            * character.sec_stats.attack_power as f32
+    fn set_wep_yellow_dmg(&mut self, sec_stats: &SecStats) {
+        self.yellow_ap_dmg =  self.normalized_speed
+            * sec_stats.attack_power as f32
             / 14.0;
-        self.mean_yellow_dmg = mean_dmg + extra_portion;
     }
 
-    fn set_hit_tables(&mut self, character: &Character) {
+    fn set_hit_tables(&mut self, prim_stats: &PrimStats, sec_stats: &SecStats) {
         if self.is_main_hand() {
-            self.set_yellow_hit_table(character);
+            self.set_yellow_hit_table(prim_stats, sec_stats);
             if self.weapon_type == WeaponType::Dagger {
                 self.set_backstab_hit_table();
             }
         }
-        self.set_white_hit_table(character);
+        self.set_white_hit_table(prim_stats, sec_stats);
+
+        // crit from talents is not part of the character stats
+        self.apply_crit_to_hit_tables(self.extra_crit);
+        self.hit_table_backstab.add_crit(self.extra_backstab_crit);
     }
 
-    fn set_yellow_hit_table(&mut self, character: &Character) {
+    fn set_yellow_hit_table(&mut self, prim_stats: &PrimStats,
+                            sec_stats: &SecStats) {
         if self.enemy_lvl == 0 {
             panic!("Simulator object must have enemy lvl before \
                    creating hit tables.");
         }
 
-        let skill_delta = self.get_skill_delta(character);
+        let skill_delta = self.get_skill_delta(prim_stats);
 
         // miss chance
         let hit_chance = self.get_effective_hit_chance_from_hit_and_skill_delta(
-            character.sec_stats.hit, skill_delta);
+            sec_stats.hit, skill_delta);
         let mut miss_chance = get_miss_chance_from_skill_delta(skill_delta);
         miss_chance = max_f32(0.0, miss_chance - hit_chance);
         self.hit_table_yellow.miss_value = miss_chance;
 
         // dodge chance
-        let dodge_chance = 0.05 + 0.001 * (skill_delta) as f32;
+        // low level targets have no negative dodge to shrink the table with
+        let dodge_chance = max_f32(0.0, 0.05 + 0.001 * skill_delta as f32);
         let dodge_value = miss_chance + dodge_chance;
         self.hit_table_yellow.dodge_value = dodge_value;
 
+        // parry and block chance, only when attacking from the front
+        let parry_value = dodge_value + self.get_parry_chance(skill_delta);
+        self.hit_table_yellow.parry_value = parry_value;
+        let block_value = parry_value + self.get_block_chance(skill_delta);
+        self.hit_table_yellow.block_value = block_value;
+
         // crit chance
-        let mut crit_chance = character.sec_stats.crit;
-        crit_chance = max_f32( 0.0,
-            crit_chance - 0.01 * (self.enemy_lvl - 60) as f32 );
-        if self.enemy_lvl == 63 {
-            crit_chance = max_f32( 0.0, crit_chance - 0.018 );
-        }
-        let crit_value = dodge_value + crit_chance;
+        let crit_chance = self.get_crit_chance(sec_stats.crit);
+        let crit_value = block_value + crit_chance;
         self.hit_table_yellow.crit_value = crit_value;
     }
 
@@ -1175,70 +1475,91 @@ impl WepSimulator {
         else { return hit; }
     }
 
-    fn get_skill_delta(&self, character: &Character) -> i32 {
-        let skill_delta: i32;
-        if self.is_off_hand() {
-            if character.oh.get_weapon_type() == WeaponType::Dagger {
-                skill_delta = 5 * self.enemy_lvl
-                    - character.prim_stats.dagger_skill;
-            } else if character.oh.get_weapon_type() == WeaponType::Sword {
-                skill_delta = 5 * self.enemy_lvl
-                    - character.prim_stats.sword_skill;
-            } else { panic!("Weapon type not implemented!"); }
-        } else {
-            if character.mh.get_weapon_type() == WeaponType::Dagger {
-                skill_delta = 5 * self.enemy_lvl
-                    - character.prim_stats.dagger_skill;
-            } else if character.mh.get_weapon_type() == WeaponType::Sword {
-                skill_delta = 5 * self.enemy_lvl
-                    - character.prim_stats.sword_skill;
-            } else { panic!("Weapon type not implemented!"); }
+    fn get_parry_chance(&self, skill_delta: i32) -> f32 {
+        if self.behind_target { return 0.0; }
+        return max_f32(0.05, 0.05 + 0.006 * skill_delta as f32);
+    }
+
+    fn get_block_chance(&self, skill_delta: i32) -> f32 {
+        if self.behind_target { return 0.0; }
+        return max_f32(0.0, 0.05 + 0.001 * skill_delta as f32);
+    }
+
+    fn get_crit_chance(&self, crit: f32) -> f32 {
+        let mut crit_chance = max_f32( 0.0,
+            crit - 0.01 * (self.enemy_lvl - 60) as f32 );
+        if self.enemy_lvl >= 63 {
+            crit_chance = max_f32( 0.0, crit_chance - 0.018 );
         }
+        return crit_chance;
+    }
+
+    fn get_skill_delta(&self, prim_stats: &PrimStats) -> i32 {
+        let skill_delta: i32;
+        if self.weapon_type == WeaponType::Dagger {
+            skill_delta = 5 * self.enemy_lvl - prim_stats.dagger_skill;
+        } else if self.weapon_type == WeaponType::Sword {
+            skill_delta = 5 * self.enemy_lvl - prim_stats.sword_skill;
+        } else { panic!("Weapon type not implemented!"); }
         return skill_delta;
     }
 
-    fn set_white_hit_table(&mut self, character: &Character) {
+    fn set_white_hit_table(&mut self, prim_stats: &PrimStats,
+                           sec_stats: &SecStats) {
         if self.enemy_lvl == 0 {
             panic!("Simulator object must have enemy lvl before \
                    creating hit tables.");
         }
 
-        let skill_delta = self.get_skill_delta(character);
+        let skill_delta = self.get_skill_delta(prim_stats);
 
         // miss chance
         let hit_chance = self.get_effective_hit_chance_from_hit_and_skill_delta(
-            character.sec_stats.hit, skill_delta);
+            sec_stats.hit, skill_delta);
         let mut miss_chance = get_miss_chance_from_skill_delta(skill_delta);
         miss_chance = 0.8 * miss_chance + 0.2;
         miss_chance = miss_chance - hit_chance;
         self.hit_table_white.miss_value = miss_chance;
 
         // dodge chance
-        let dodge_chance = 0.05 + 0.001 * (skill_delta) as f32;
+        // low level targets have no negative dodge to shrink the table with
+        let dodge_chance = max_f32(0.0, 0.05 + 0.001 * skill_delta as f32);
         let dodge_value = miss_chance + dodge_chance;
         self.hit_table_white.dodge_value = dodge_value;
 
-        // glancing chance
-        if self.enemy_lvl < 60 || self.enemy_lvl > 63 {
-            panic!("No reliable glancing numbers outside 60-63");
-        }
-        let glancing_chance = 0.1 + 0.1 * (self.enemy_lvl - 60) as f32;
-        let glancing_value = dodge_value + glancing_chance;
+        // parry chance, only when attacking from the front
+        let parry_value = dodge_value + self.get_parry_chance(skill_delta);
+        self.hit_table_white.parry_value = parry_value;
+
+        // glancing chance, no glancing blows on lower level targets and
+        // level 63 numbers are used for anything above
+        let level_delta = min_i32(self.enemy_lvl, 63) - 60;
+        let glancing_chance = max_f32(0.0, 0.1 + 0.1 * level_delta as f32);
+        let glancing_value = parry_value + glancing_chance;
         self.hit_table_white.glancing_value = glancing_value;
 
+        // block chance, only when attacking from the front
+        let block_value = glancing_value + self.get_block_chance(skill_delta);
+        self.hit_table_white.block_value = block_value;
+
         // crit chance
-        let mut crit_chance = character.sec_stats.crit;
-        crit_chance = max_f32( 0.0,
-            crit_chance - 0.01 * (self.enemy_lvl - 60) as f32 );
-        if self.enemy_lvl == 63 {
-            crit_chance = max_f32( 0.0, crit_chance - 0.018 );
-        }
-        let crit_value = glancing_value + crit_chance;
+        let crit_chance = self.get_crit_chance(sec_stats.crit);
+        let crit_value = block_value + crit_chance;
         self.hit_table_white.crit_value = crit_value;
 
     }
 
     fn add_crit(&mut self, crit: f32) {
+        self.extra_crit += crit;
+        self.apply_crit_to_hit_tables(crit);
+    }
+
+    fn add_backstab_crit(&mut self, crit: f32) {
+        self.extra_backstab_crit += crit;
+        self.hit_table_backstab.add_crit(crit);
+    }
+
+    fn apply_crit_to_hit_tables(&mut self, crit: f32) {
         self.hit_table_white.add_crit(crit);
         if self.is_main_hand() {
             self.hit_table_yellow.add_crit(crit);
@@ -1266,8 +1587,7 @@ impl WepSimulator {
 fn get_miss_chance_from_skill_delta(delta: i32) -> f32 {
     if delta < 0 { return 0.05; }
     else if delta <= 10 && delta >= 0 { return 0.05 + 0.001 * delta as f32; }
-    else if delta <= 15 { return 0.07 + 0.002 * ((delta - 10) as f32); }
-    else { panic!("Level difference not implemented"); }
+    else { return 0.07 + 0.002 * ((delta - 10) as f32); }
 }
 
 #[derive(Debug,Clone)]
@@ -1276,6 +1596,8 @@ struct YellowHitTable {
     // that number determines the hit type
     miss_value: f32,
     dodge_value: f32,
+    parry_value: f32,
+    block_value: f32,
     crit_value: f32
 }
 
@@ -1284,14 +1606,18 @@ impl YellowHitTable {
         YellowHitTable {
             miss_value: 0.0,
             dodge_value: 0.0,
+            parry_value: 0.0,
+            block_value: 0.0,
             crit_value: 0.0
         }
     }
 
-    fn roll_for_hit(&self) -> Hit {
-        let die = roll_die();
+    fn roll_for_hit(&self, rng: &mut StdRng) -> Hit {
+        let die = roll_die(rng);
         if die < self.miss_value { return Hit::Miss; }
         else if die < self.dodge_value { return Hit::Dodge; }
+        else if die < self.parry_value { return Hit::Parry; }
+        else if die < self.block_value { return Hit::Block; }
         else if die < self.crit_value { return Hit::Crit; }
         else { return Hit::Hit; }
     }
@@ -1304,8 +1630,12 @@ impl YellowHitTable {
         println!("Miss chance:\t\t{:.1}%", 100.0 * self.miss_value);
         println!("Dodge chance:\t\t{:.1}%",
                  100.0 * (self.dodge_value - self.miss_value));
+        println!("Parry chance:\t\t{:.1}%",
+                 100.0 * (self.parry_value - self.dodge_value));
+        println!("Block chance:\t\t{:.1}%",
+                 100.0 * (self.block_value - self.parry_value));
         println!("Crit chance:\t\t{:.1}%",
-                 100.0 * (self.crit_value - self.dodge_value));
+                 100.0 * (self.crit_value - self.block_value));
         println!("Hit chance:\t\t{:.1}%",
                  100.0 * (1.0 - self.crit_value));
     }
@@ -1316,7 +1646,9 @@ impl YellowHitTable {
 struct WhiteHitTable {
     miss_value: f32,
     dodge_value: f32,
+    parry_value: f32,
     glancing_value: f32,
+    block_value: f32,
     crit_value: f32
 }
 
@@ -1325,16 +1657,20 @@ impl WhiteHitTable {
         WhiteHitTable {
             miss_value: 0.0,
             dodge_value: 0.0,
+            parry_value: 0.0,
             glancing_value: 0.0,
+            block_value: 0.0,
             crit_value: 0.0
         }
     }
 
-    fn roll_for_hit(&self) -> Hit {
-        let die = roll_die();
+    fn roll_for_hit(&self, rng: &mut StdRng) -> Hit {
+        let die = roll_die(rng);
         if die < self.miss_value { return Hit::Miss; }
         else if die < self.dodge_value { return Hit::Dodge; }
+        else if die < self.parry_value { return Hit::Parry; }
         else if die < self.glancing_value { return Hit::Glancing; }
+        else if die < self.block_value { return Hit::Block; }
         else if die < self.crit_value { return Hit::Crit; }
         else { return Hit::Hit; }
     }
@@ -1347,10 +1683,14 @@ impl WhiteHitTable {
         println!("Miss chance:\t\t{:.1}%", 100.0 * self.miss_value);
         println!("Dodge chance:\t\t{:.1}%",
                  100.0 * (self.dodge_value - self.miss_value));
+        println!("Parry chance:\t\t{:.1}%",
+                 100.0 * (self.parry_value - self.dodge_value));
         println!("Glancing chance:\t{:.1}%",
-                 100.0 * (self.glancing_value - self.dodge_value));
+                 100.0 * (self.glancing_value - self.parry_value));
+        println!("Block chance:\t\t{:.1}%",
+                 100.0 * (self.block_value - self.glancing_value));
         println!("Crit chance:\t\t{:.1}%",
-                 100.0 * (self.crit_value - self.glancing_value));
+                 100.0 * (self.crit_value - self.block_value));
         println!("Hit chance:\t\t{:.1}%",
                  100.0 * (1.0 - self.crit_value));
     }
@@ -1362,7 +1702,8 @@ struct Modifiers {
     hit: HitModifiers,
     crit: CritModifiers,
     finisher: FinisherModifiers,
-    armor_factor: f32
+    armor_factor: f32,
+    block_value: f32
 }
 
 impl Modifiers {
@@ -1372,7 +1713,8 @@ impl Modifiers {
             hit: HitModifiers::new(),
             crit: CritModifiers::new(),
             finisher: FinisherModifiers::new(),
-            armor_factor: 1.0
+            armor_factor: 1.0,
+            block_value: 0.0
         }
     }
 
@@ -1380,19 +1722,27 @@ impl Modifiers {
         return dmg * self.armor_factor;
     }
 
+    fn block_reduction(&self, dmg: f32) -> f32 {
+        return max_f32(0.0, dmg - self.block_value);
+    }
+
     fn set_modifiers(&mut self, character: &Character) {
         self.general.set_modifiers(character);
-        self.set_armor_factor();
     }
 
-    fn set_armor_factor(&mut self) {
-        let mut armor = 3731.0;
-        // 5 sunder armor stacks
-        armor -= 2250.0;
-        // CoR
-        armor -= 640.0;
-        // Faerie fire
-        armor -= 505.0;
+    fn set_target_modifiers(&mut self, target: &Target) {
+        self.set_armor_factor(target);
+        self.block_value = target.block_value;
+    }
+
+    fn set_armor_factor(&mut self, target: &Target) {
+        let mut armor = target.armor as f32;
+        if target.debuffs.sunder_armor {
+            // 5 stacks
+            armor -= 2250.0;
+        }
+        if target.debuffs.curse_of_recklessness { armor -= 640.0; }
+        if target.debuffs.faerie_fire { armor -= 505.0; }
         armor = max_f32(armor, 0.0);
         let x = armor / (85.0 * 60.0 + 40.0);
         let red = x / (1.0 + x);
@@ -1488,14 +1838,14 @@ impl FinisherModifiers {
         }
     }
 
-    fn gets_extra_combo_point(&self) -> bool {
-        let die = roll_die();
+    fn gets_extra_combo_point(&self, rng: &mut StdRng) -> bool {
+        let die = roll_die(rng);
         if die < self.add_combo_point_chance { return true; }
         else { return false; }
     }
 
-    fn gets_extra_energy(&self, combo_points: i32) -> bool {
-        let die = roll_die();
+    fn gets_extra_energy(&self, rng: &mut StdRng, combo_points: i32) -> bool {
+        let die = roll_die(rng);
         if die < combo_points as f32
             * self.restore_energy_chance_per_combo_point {
             return true;
@@ -1511,9 +1861,9 @@ enum Rotation {
     None,
 }
 
-#[derive(Display,PartialEq)]
+#[derive(Clone,Copy,Debug,Display,PartialEq,Serialize)]
 pub enum Hit {
-    Hit, Crit, Miss, Glancing, Dodge
+    Hit, Crit, Miss, Glancing, Dodge, Parry, Block
 }
 
 
@@ -1523,3 +1873,114 @@ pub enum WeaponSlot {
     Oh,
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combat_log::{CombatLogger,LogEntry};
+    use crate::stats::OverallStats;
+    use rand::Rng;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct ExtraAttackLogger {
+        // times of the extra attack proccs and of the swings they gave
+        times: Rc<RefCell<(Vec<f32>, Vec<f32>)>>
+    }
+
+    impl CombatLogger for ExtraAttackLogger {
+        fn log(&mut self, entry: &LogEntry) {
+            let mut times = self.times.borrow_mut();
+            match entry.event {
+                CombatEvent::Procc { effect: ProccEffect::ExtraAttack, .. } =>
+                    times.0.push(entry.time),
+                CombatEvent::Swing { extra_attack: Some(_), .. } =>
+                    times.1.push(entry.time),
+                _ => ()
+            }
+        }
+    }
+
+    fn get_rolls(simulator: &mut Simulator, iteration: u64) -> Vec<f32> {
+        simulator.seed_rng_for_iteration(iteration);
+        return (0..8).map(|_| simulator.rng.gen::<f32>()).collect();
+    }
+
+    fn get_seeded_simulator(seed: u64) -> Simulator {
+        let mut simulator = Simulator::new();
+        simulator.seed = seed;
+        return simulator;
+    }
+
+    #[test]
+    fn same_seed_and_iteration_give_same_rolls() {
+        let mut a = get_seeded_simulator(7);
+        let mut b = get_seeded_simulator(7);
+        assert_eq!(get_rolls(&mut a, 3), get_rolls(&mut b, 3));
+    }
+
+    #[test]
+    fn rolls_do_not_depend_on_earlier_iterations() {
+        // what matters for spreading iterations over threads
+        let mut fresh = get_seeded_simulator(7);
+        let mut used = get_seeded_simulator(7);
+        for iteration in 0..5 { get_rolls(&mut used, iteration); }
+        assert_eq!(get_rolls(&mut fresh, 3), get_rolls(&mut used, 3));
+    }
+
+    #[test]
+    fn seeds_and_iterations_give_different_rolls() {
+        let mut simulator = get_seeded_simulator(7);
+        let rolls = get_rolls(&mut simulator, 3);
+        assert_ne!(rolls, get_rolls(&mut simulator, 4));
+        let mut other_seed = get_seeded_simulator(8);
+        assert_ne!(rolls, get_rolls(&mut other_seed, 3));
+    }
+
+    #[test]
+    fn seeded_fights_repeat() {
+        let mut args = Args::default_args();
+        args.spec_file = "configs/current.yaml".to_string();
+        args.fight_length = 60.0;
+        args.seed = 11;
+        let mut character = Character::create_character(&args);
+        character.convert_stats_and_set_cooldowns();
+
+        let mut all_dps = Vec::new();
+        for _ in 0..2 {
+            let mut simulator = Simulator::new();
+            simulator.apply_input_arguments(&args);
+            simulator.configure_with_character(&character);
+            let mut stats = OverallStats::new_from_args(&args);
+            for iteration in 0..20 {
+                simulator.simulate(iteration);
+                stats.import_current_data(simulator.get_stats());
+            }
+            all_dps.push(stats.get_mean_dps());
+        }
+        assert_eq!(all_dps[0], all_dps[1]);
+    }
+
+    #[test]
+    fn extra_attacks_swing_when_they_procc() {
+        // sinister strike proccs sword specialization as well as white hits
+        let mut args = Args::default_args();
+        args.spec_file = "configs/sword.yaml".to_string();
+        args.fight_length = 60.0;
+        let mut character = Character::create_character(&args);
+        character.convert_stats_and_set_cooldowns();
+
+        let mut simulator = Simulator::new();
+        simulator.apply_input_arguments(&args);
+        simulator.configure_with_character(&character);
+        let times = Rc::new(RefCell::new((Vec::new(), Vec::new())));
+        simulator.log.set_logger(Some(Box::new(ExtraAttackLogger {
+            times: times.clone() })), args.fight_length);
+        for iteration in 0..20 { simulator.simulate(iteration); }
+
+        let (procc_times, swing_times) = &*times.borrow();
+        assert!(! procc_times.is_empty());
+        assert_eq!(procc_times, swing_times);
+    }
+}
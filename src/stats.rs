@@ -1,14 +1,24 @@
 use crate::armory::HitProcc;
-use crate::simulator::Hit;
-use crate::utils::{Args,mean,std_dev};
+use crate::output::{AbilityReport,ComparedSpecReport,CompareReport,
+DpsPercentiles,HitTypeCounts,MeanWithError,
+NamedRatio,NamedValue,ResourceReport,SimulationReport,StatWeightReport,
+TimelineBinReport,SCHEMA_VERSION};
+use crate::simulator::{Hit,WeaponSlot,get_stat_buff_uptime_name};
+use crate::utils::{Args,mean,percentile,std_dev};
 
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter,Write};
+
+const HISTOGRAM_BINS: usize = 15;
+const HISTOGRAM_WIDTH: usize = 50;
 
 
 #[derive(Debug)]
 pub struct OverallStats {
     n_runs: i32,
     fight_length: f32,
+    seed: u64,
     dps: Vec<f32>,
     backstab_ratio: Vec<f32>,
     sinister_strike_ratio: Vec<f32>,
@@ -16,14 +26,24 @@ pub struct OverallStats {
     mh_white_ratio: Vec<f32>,
     oh_white_ratio: Vec<f32>,
     procc_dps_ratios: HashMap<String,Vec<f32>>,
+    buff_uptime_ratios: HashMap<String,Vec<f32>>,
+    cd_activations: HashMap<String,Vec<f32>>,
+    energy_wasted: HashMap<String,Vec<f32>>,
+    energy_cap_ratio: Vec<f32>,
+    avg_energy: Vec<f32>,
+    combo_points_wasted: Vec<f32>,
+    timeline: Vec<TimelineBin>, // summed over all runs
+    timeline_bin_width: f32,
+    totals: CurrentStats, // summed over all runs
     weights_text: String
 }
 
 impl OverallStats {
     pub fn new_from_args(args: &Args) -> OverallStats {
         OverallStats {
-            n_runs: args.iterations,
+            n_runs: 0,
             fight_length: args.fight_length,
+            seed: args.seed,
             dps: Vec::new(),
             backstab_ratio: Vec::new(),
             sinister_strike_ratio: Vec::new(),
@@ -31,11 +51,22 @@ impl OverallStats {
             mh_white_ratio: Vec::new(),
             oh_white_ratio: Vec::new(),
             procc_dps_ratios: HashMap::new(),
+            buff_uptime_ratios: HashMap::new(),
+            cd_activations: HashMap::new(),
+            energy_wasted: HashMap::new(),
+            energy_cap_ratio: Vec::new(),
+            avg_energy: Vec::new(),
+            combo_points_wasted: Vec::new(),
+            timeline: Vec::new(),
+            timeline_bin_width: args.timeline_bin_width,
+            totals: CurrentStats::new(),
             weights_text: "".to_string()
         }
     }
 
     pub fn import_current_data(&mut self, stats: CurrentStats) {
+        self.n_runs += 1;
+        self.totals.add(&stats);
         self.dps.push(stats.dmg / stats.fight_length);
         self.backstab_ratio.push(stats.backstab.dmg / stats.dmg);
         self.sinister_strike_ratio.push(stats.sinister_strike.dmg / stats.dmg);
@@ -48,10 +79,120 @@ impl OverallStats {
                 .or_insert(Vec::new());
             cur_vec.push(dmg_and_count.dmg / stats.dmg);
         }
+
+        for (name, uptime) in &stats.buff_uptime {
+            let cur_vec = self.buff_uptime_ratios.entry(name.to_string())
+                .or_default();
+            cur_vec.push(uptime / stats.fight_length);
+        }
+
+        for (name, count) in &stats.cd_activations {
+            let cur_vec = self.cd_activations.entry(name.to_string())
+                .or_default();
+            cur_vec.push(*count as f32);
+        }
+
+        for (name, wasted) in &stats.energy_wasted {
+            let cur_vec = self.energy_wasted.entry(name.to_string())
+                .or_default();
+            cur_vec.push(*wasted as f32);
+        }
+        self.energy_cap_ratio.push(stats.time_at_energy_cap 
+                                   / stats.fight_length);
+        self.avg_energy.push(stats.energy_time_integral / stats.fight_length);
+        self.combo_points_wasted.push(stats.combo_points_wasted as f32);
+        add_timeline(&mut self.timeline, &stats.timeline);
+    }
+
+    pub fn merge(&mut self, other: OverallStats) {
+        // appends the runs gathered by another worker thread or batch
+        self.n_runs += other.n_runs;
+        self.totals.add(&other.totals);
+        self.dps.extend(other.dps);
+        self.backstab_ratio.extend(other.backstab_ratio);
+        self.sinister_strike_ratio.extend(other.sinister_strike_ratio);
+        self.eviscerate_ratio.extend(other.eviscerate_ratio);
+        self.mh_white_ratio.extend(other.mh_white_ratio);
+        self.oh_white_ratio.extend(other.oh_white_ratio);
+
+        for (name, ratios) in other.procc_dps_ratios {
+            self.procc_dps_ratios.entry(name).or_default()
+                .extend(ratios);
+        }
+        for (name, ratios) in other.buff_uptime_ratios {
+            self.buff_uptime_ratios.entry(name).or_default()
+                .extend(ratios);
+        }
+        for (name, counts) in other.cd_activations {
+            self.cd_activations.entry(name).or_default()
+                .extend(counts);
+        }
+        for (name, wasted) in other.energy_wasted {
+            self.energy_wasted.entry(name).or_default()
+                .extend(wasted);
+        }
+        self.energy_cap_ratio.extend(other.energy_cap_ratio);
+        self.avg_energy.extend(other.avg_energy);
+        self.combo_points_wasted.extend(other.combo_points_wasted);
+        add_timeline(&mut self.timeline, &other.timeline);
     }
 
     pub fn get_mean_dps(&self) -> f32 { return mean(&self.dps); }
 
+    pub fn get_dps_half_width(&self) -> f32 {
+        return get_half_width(&self.dps);
+    }
+
+    fn get_paired_dps_diffs(&self, default: &OverallStats) -> Vec<f32> {
+        // iteration i of both runs used the same seed
+        let mut diffs: Vec<f32> = Vec::new();
+        for (dps, default_dps) in self.dps.iter().zip(default.dps.iter()) {
+            diffs.push(dps - default_dps);
+        }
+        return diffs;
+    }
+
+    pub fn get_dps_diff_half_width(&self, default: &OverallStats) -> f32 {
+        return get_half_width(&self.get_paired_dps_diffs(default));
+    }
+
+    pub fn get_compared_spec_report(&self, spec_file: &str, 
+                                    default: &OverallStats) 
+        -> ComparedSpecReport {
+        let default_dps = default.get_mean_dps();
+        let diff_dps = mean(&self.get_paired_dps_diffs(default));
+        let diff_dps_std = self.get_dps_diff_half_width(default);
+        ComparedSpecReport {
+            spec_file: spec_file.to_string(),
+            dps: MeanWithError {
+                mean: self.get_mean_dps(),
+                ci95: self.get_dps_half_width()
+            },
+            dps_diff: MeanWithError { mean: diff_dps, ci95: diff_dps_std },
+            dps_diff_ratio: MeanWithError {
+                mean: diff_dps / default_dps,
+                ci95: diff_dps_std / default_dps
+            },
+            significant: diff_dps.abs() > diff_dps_std
+        }
+    }
+
+    pub fn get_compare_report(all_stats: &[OverallStats], 
+                              spec_files: &[String]) -> CompareReport {
+        let mut specs = Vec::new();
+        for (stats, spec_file) in all_stats.iter().zip(spec_files.iter()) {
+            specs.push(stats.get_compared_spec_report(spec_file, 
+                                                      &all_stats[0]));
+        }
+        CompareReport {
+            schema_version: SCHEMA_VERSION,
+            iterations: all_stats[0].n_runs,
+            fight_length: all_stats[0].fight_length,
+            seed: all_stats[0].seed,
+            specs
+        }
+    }
+
     pub fn add_weights_text(&mut self, text: &String) {
         self.weights_text = text.to_string();
     }
@@ -66,19 +207,293 @@ impl OverallStats {
                mean_dps_std);
     }
 
-    pub fn print_stat_weight_minus_default_dps(&self, default_dps: f32) {
+    pub fn print_stat_weight_minus_default_dps(&self, default: &OverallStats) {
 
-        let mean_dps = mean(&self.dps);
-        let diff_dps = mean_dps - default_dps;
-        let dps_within_std = std_dev(&self.dps);
-        let mean_dps_std = 1.96 * dps_within_std / (self.n_runs as f32).sqrt();
-        let mean_dps_diff_std = 1.41 * mean_dps_std;
+        let default_dps = default.get_mean_dps();
+        let diff_dps = mean(&self.get_paired_dps_diffs(default));
+        let mean_dps_diff_std = self.get_dps_diff_half_width(default);
 
         println!("{}{:+.2}% ±{:.2}%", self.weights_text, 
                  100.0 * diff_dps / default_dps, 
                  100.0 * mean_dps_diff_std / default_dps);
     }
 
+    fn get_named_ratios(ratios: &HashMap<String,Vec<f32>>) 
+        -> Vec<NamedRatio> {
+        let mut names: Vec<&String> = ratios.keys().collect();
+        names.sort();
+        let mut named_ratios = Vec::new();
+        for name in names {
+            let values = &ratios[name];
+            named_ratios.push(NamedRatio {
+                name: name.to_string(),
+                mean: mean(values),
+                ci95: get_half_width(values)
+            });
+        }
+        return named_ratios;
+    }
+
+    pub fn get_report(&self) -> SimulationReport {
+        let mut damage_shares = Vec::new();
+        let ability_ratios = vec![
+            ("Backstab", &self.backstab_ratio),
+            ("Sinister strike", &self.sinister_strike_ratio),
+            ("Eviscerate", &self.eviscerate_ratio),
+            ("MH white", &self.mh_white_ratio),
+            ("OH white", &self.oh_white_ratio)
+        ];
+        for (name, ratios) in ability_ratios {
+            if mean(ratios) > 0.0 {
+                damage_shares.push(NamedRatio {
+                    name: name.to_string(),
+                    mean: mean(ratios),
+                    ci95: get_half_width(ratios)
+                });
+            }
+        }
+
+        SimulationReport {
+            schema_version: SCHEMA_VERSION,
+            iterations: self.n_runs,
+            fight_length: self.fight_length,
+            seed: self.seed,
+            dps: MeanWithError {
+                mean: mean(&self.dps),
+                ci95: self.get_dps_half_width()
+            },
+            damage_shares,
+            procc_damage_shares: OverallStats::get_named_ratios(
+                &self.procc_dps_ratios),
+            buff_uptimes: OverallStats::get_named_ratios(
+                &self.buff_uptime_ratios),
+            cooldown_activations: OverallStats::get_named_ratios(
+                &self.cd_activations),
+            resources: self.get_resource_report(),
+            timeline: self.get_timeline_report(),
+            dps_percentiles: self.get_dps_percentiles(),
+            abilities: self.totals.get_ability_reports(
+                self.n_runs as f32 * self.fight_length / 60.0),
+            stat_weights: Vec::new()
+        }
+    }
+
+    fn get_resource_report(&self) -> ResourceReport {
+        ResourceReport {
+            energy_wasted: OverallStats::get_named_ratios(&self.energy_wasted),
+            time_at_energy_cap: MeanWithError {
+                mean: mean(&self.energy_cap_ratio),
+                ci95: get_half_width(&self.energy_cap_ratio)
+            },
+            avg_energy: MeanWithError {
+                mean: mean(&self.avg_energy),
+                ci95: get_half_width(&self.avg_energy)
+            },
+            combo_points_wasted: MeanWithError {
+                mean: mean(&self.combo_points_wasted),
+                ci95: get_half_width(&self.combo_points_wasted)
+            }
+        }
+    }
+
+    pub fn get_timeline_report(&self) -> Vec<TimelineBinReport> {
+        // averages of the runs, every bin as seen in a single fight
+        let mut reports = Vec::new();
+        let mut cumulative_dmg = 0.0;
+        for (i, bin) in self.timeline.iter().enumerate() {
+            let start = i as f32 * self.timeline_bin_width;
+            let end = (start + self.timeline_bin_width).min(self.fight_length);
+            let width = end - start;
+            let dmg = bin.dmg / self.n_runs as f32;
+            cumulative_dmg += dmg;
+
+            let mut names: Vec<&String> = bin.buff_uptime.keys().collect();
+            names.sort();
+            let mut buff_uptimes = Vec::new();
+            for name in names {
+                buff_uptimes.push(NamedValue {
+                    name: name.to_string(),
+                    value: bin.buff_uptime[name] / (self.n_runs as f32 * width)
+                });
+            }
+
+            reports.push(TimelineBinReport {
+                start,
+                end,
+                dps: dmg / width,
+                cumulative_dps: cumulative_dmg / end,
+                avg_energy: bin.energy_integral / (self.n_runs as f32 * width),
+                buff_uptimes
+            });
+        }
+        return reports;
+    }
+
+    pub fn print_timeline(&self) {
+        let reports = self.get_timeline_report();
+        if reports.is_empty() { return; }
+
+        println!("\nTimeline:");
+        print!("{:>13}{:>9}{:>12}{:>8}", "Time", "Dps", "Cumulative", 
+               "Energy");
+        for buff in &reports[0].buff_uptimes { print!("  {}", buff.name); }
+        println!();
+        for report in &reports {
+            print!("{:>5.0} - {:>5.0}{:>9.1}{:>12.1}{:>8.1}", report.start,
+                   report.end, report.dps, report.cumulative_dps, 
+                   report.avg_energy);
+            for buff in &report.buff_uptimes {
+                print!("  {:>w$.0}%", 100.0 * buff.value, 
+                       w = buff.name.len() - 1);
+            }
+            println!();
+        }
+    }
+
+    pub fn write_timeline_csv(&self, path: &str) -> std::io::Result<()> {
+        let reports = self.get_timeline_report();
+        let mut file = BufWriter::new(File::create(path)?);
+        write!(file, "start,end,dps,cumulative_dps,avg_energy")?;
+        if let Some(report) = reports.first() {
+            for buff in &report.buff_uptimes {
+                write!(file, ",{} uptime", buff.name)?;
+            }
+        }
+        writeln!(file)?;
+        for report in &reports {
+            write!(file, "{},{},{},{},{}", report.start, report.end, 
+                   report.dps, report.cumulative_dps, report.avg_energy)?;
+            for buff in &report.buff_uptimes {
+                write!(file, ",{}", buff.value)?;
+            }
+            writeln!(file)?;
+        }
+        return Ok(());
+    }
+
+    fn print_resources(&self) {
+        let resources = self.get_resource_report();
+        println!("\nResources per fight:");
+        println!("Average energy:\t\t{:>6.1}", resources.avg_energy.mean);
+        println!("Time at energy cap:\t{:>6.2}% ±{:.2}%", 
+                 100.0 * resources.time_at_energy_cap.mean,
+                 100.0 * resources.time_at_energy_cap.ci95);
+        for wasted in resources.energy_wasted {
+            if wasted.mean > 0.0 {
+                println!("Energy wasted ({}):\t{:>6.1} ±{:.1}", wasted.name,
+                         wasted.mean, wasted.ci95);
+            }
+        }
+        println!("Combo points wasted:\t{:>6.2} ±{:.2}", 
+                 resources.combo_points_wasted.mean,
+                 resources.combo_points_wasted.ci95);
+    }
+
+    pub fn get_stat_weight_report(&self, default: &OverallStats) 
+        -> StatWeightReport {
+        let default_dps = default.get_mean_dps();
+        let diff_dps = mean(&self.get_paired_dps_diffs(default));
+        let diff_dps_std = self.get_dps_diff_half_width(default);
+        StatWeightReport {
+            name: self.weights_text.trim().trim_end_matches(':').to_string(),
+            dps_diff: MeanWithError { mean: diff_dps, ci95: diff_dps_std },
+            dps_diff_ratio: MeanWithError {
+                mean: diff_dps / default_dps,
+                ci95: diff_dps_std / default_dps
+            }
+        }
+    }
+
+    fn get_dps_percentiles(&self) -> DpsPercentiles {
+        let mut sorted_dps = self.dps.clone();
+        sorted_dps.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        DpsPercentiles {
+            min: percentile(&sorted_dps, 0.0),
+            p5: percentile(&sorted_dps, 0.05),
+            median: percentile(&sorted_dps, 0.5),
+            p95: percentile(&sorted_dps, 0.95),
+            max: percentile(&sorted_dps, 1.0)
+        }
+    }
+
+    fn print_dps_distribution(&self) {
+        let percentiles = self.get_dps_percentiles();
+        println!("\nDps distribution:");
+        println!("Min: {:.0}  5th: {:.0}  Median: {:.0}  95th: {:.0}  \
+                 Max: {:.0}", percentiles.min, percentiles.p5, 
+                 percentiles.median, percentiles.p95, percentiles.max);
+
+        let histogram = self.get_dps_histogram();
+        if histogram.is_empty() { return; }
+
+        let max_count = histogram.iter().map(|bin| bin.2).max().unwrap();
+        for (start, end, count) in histogram {
+            let bar_length = count * HISTOGRAM_WIDTH / max_count;
+            println!("{:>6.0} - {:>6.0} |{:<width$}| {}", start, end,
+                     "#".repeat(bar_length), count, width = HISTOGRAM_WIDTH);
+        }
+    }
+
+    pub fn get_dps_histogram(&self) -> Vec<(f32, f32, usize)> {
+        // (start, end, count) of every bin, empty if all runs had equal dps
+        let percentiles = self.get_dps_percentiles();
+        let bin_width = (percentiles.max - percentiles.min) 
+            / HISTOGRAM_BINS as f32;
+        if bin_width <= 0.0 { return Vec::new(); }
+
+        let mut counts = vec![0; HISTOGRAM_BINS];
+        for dps in &self.dps {
+            let mut bin = ((dps - percentiles.min) / bin_width) as usize;
+            if bin >= HISTOGRAM_BINS { bin = HISTOGRAM_BINS - 1; }
+            counts[bin] += 1;
+        }
+        let mut histogram = Vec::new();
+        for (i, count) in counts.into_iter().enumerate() {
+            histogram.push((percentiles.min + i as f32 * bin_width,
+                            percentiles.min + (i + 1) as f32 * bin_width,
+                            count));
+        }
+        return histogram;
+    }
+
+    pub fn write_csv(&self, path: &str) -> std::io::Result<()> {
+        // one row per iteration with the dps done by every ability and procc
+        let mut procc_names: Vec<&String> = self.procc_dps_ratios.keys()
+            .collect();
+        procc_names.sort();
+        let mut uptime_names: Vec<&String> = self.buff_uptime_ratios.keys()
+            .collect();
+        uptime_names.sort();
+
+        let mut file = BufWriter::new(File::create(path)?);
+        write!(file, "iteration,dps,backstab_dps,sinister_strike_dps,\
+               eviscerate_dps,mh_white_dps,oh_white_dps")?;
+        for name in &procc_names { write!(file, ",{} dps", name)?; }
+        for name in &uptime_names { write!(file, ",{} uptime", name)?; }
+        write!(file, ",avg_energy,energy_cap_ratio,combo_points_wasted")?;
+        writeln!(file)?;
+
+        for i in 0..self.dps.len() {
+            let dps = self.dps[i];
+            write!(file, "{},{},{},{},{},{},{}", i, dps,
+                   dps * self.backstab_ratio[i],
+                   dps * self.sinister_strike_ratio[i],
+                   dps * self.eviscerate_ratio[i],
+                   dps * self.mh_white_ratio[i],
+                   dps * self.oh_white_ratio[i])?;
+            for name in &procc_names {
+                write!(file, ",{}", dps * self.procc_dps_ratios[*name][i])?;
+            }
+            for name in &uptime_names {
+                write!(file, ",{}", self.buff_uptime_ratios[*name][i])?;
+            }
+            write!(file, ",{},{},{}", self.avg_energy[i], 
+                   self.energy_cap_ratio[i], self.combo_points_wasted[i])?;
+            writeln!(file)?;
+        }
+        return Ok(());
+    }
+
     pub fn print(&self) {
 
         let mean_dps = mean(&self.dps);
@@ -115,6 +530,7 @@ impl OverallStats {
 
         println!("\nStatistics over dps gathered from {} iterations of \
                   {}s each.", self.n_runs, self.fight_length);
+        println!("Seed:\t\t{}", self.seed);
         println!("Mean Dps:\t{:>8.2}  ±{:.2}", mean_dps, mean_dps_std);
 
         if mean_backstab_ratio > 0.0 {
@@ -124,7 +540,7 @@ impl OverallStats {
         }
 
         if mean_sinister_strike_ratio > 0.0 {
-            println!("Sinister strike:\t{:>8.2}% ±{:.2}", 
+            println!("Sinister strike:\t{:>8.2}% ±{:.2}%", 
                      100.0 * mean_sinister_strike_ratio, 
                      100.0 * mean_sinister_strike_ratio_std);
         }
@@ -142,7 +558,12 @@ impl OverallStats {
                      100.0 * mean_eviscerate_ratio_std);
         }
 
-        for (name, dps_ratios) in &self.procc_dps_ratios {
+        // sorted, so that a seed always prints the same text
+        let mut procc_names: Vec<&String> = self.procc_dps_ratios.keys()
+            .collect();
+        procc_names.sort();
+        for name in procc_names {
+            let dps_ratios = &self.procc_dps_ratios[name];
 
             let mean_procc_dps_ratio = mean(&dps_ratios);
             let procc_dps_within_std = std_dev(dps_ratios);
@@ -155,9 +576,53 @@ impl OverallStats {
                          100.0 * mean_procc_dps_ratio_std);
             }
         }
+
+        for uptime in OverallStats::get_named_ratios(&self.buff_uptime_ratios) {
+            println!("{} uptime:\t{:>8.2}% ±{:.2}%", uptime.name,
+                     100.0 * uptime.mean, 100.0 * uptime.ci95);
+        }
+
+        for activations in OverallStats::get_named_ratios(&self.cd_activations) {
+            println!("{} uses per fight:\t{:>5.2}", activations.name,
+                     activations.mean);
+        }
+
+        self.print_resources();
+        self.print_ability_breakdown();
+        self.print_dps_distribution();
+        self.print_timeline();
+    }
+
+    fn print_ability_breakdown(&self) {
+        let reports = self.totals.get_ability_reports(
+            self.n_runs as f32 * self.fight_length / 60.0);
+
+        println!("\nAbility breakdown:");
+        println!("{:<18}{:>10}{:>9}{:>9}{:>7}{:>7}{:>7}{:>11}", "", 
+                 "Casts/min", "Avg hit", "Avg crit", "Crit", "Miss", 
+                 "Dodge", "Dmg/energy");
+        for report in reports {
+            println!("{:<18}{:>10.2}{:>9.0}{:>9.0}{:>6.1}%{:>6.1}%{:>6.1}%\
+                     {:>11.2}", report.name, report.casts_per_minute, 
+                     report.avg_hit_dmg, report.avg_crit_dmg, 
+                     100.0 * report.crit_rate, 100.0 * report.miss_rate, 
+                     100.0 * report.dodge_rate, report.dmg_per_energy);
+        }
+    }
+}
+
+fn add_timeline(timeline: &mut Vec<TimelineBin>, other: &[TimelineBin]) {
+    if timeline.is_empty() { *timeline = other.to_vec(); return; }
+    for (bin, other_bin) in timeline.iter_mut().zip(other.iter()) {
+        bin.add(other_bin);
     }
 }
 
+fn get_half_width(values: &Vec<f32>) -> f32 {
+    // half width of the 95% confidence interval of the mean
+    return 1.96 * std_dev(values) / (values.len() as f32).sqrt();
+}
+
 #[derive(Clone,Debug)]
 pub struct CurrentStats {
     dmg: f32,
@@ -165,9 +630,20 @@ pub struct CurrentStats {
     backstab: OneAttackStats,
     sinister_strike: OneAttackStats,
     eviscerate: OneAttackStats,
+    slice_and_dice: OneAttackStats,
     mh_white: OneAttackStats,
     oh_white: OneAttackStats,
-    proccs: HashMap<String,DamageAndCount>
+    extra_attacks: OneAttackStats,
+    proccs: HashMap<String,DamageAndCount>,
+    buff_uptime: HashMap<String,f32>,
+    cd_activations: HashMap<String,i32>,
+    energy_wasted: HashMap<String,i32>, // by source
+    time_at_energy_cap: f32,
+    energy_time_integral: f32,
+    combo_points_wasted: i32,
+    now: f32,
+    timeline_bin_width: f32, // zero when no timeline is recorded
+    timeline: Vec<TimelineBin>
 }
 
 impl CurrentStats {
@@ -178,9 +654,20 @@ impl CurrentStats {
             backstab: OneAttackStats::new(),
             sinister_strike: OneAttackStats::new(),
             eviscerate: OneAttackStats::new(),
+            slice_and_dice: OneAttackStats::new(),
             mh_white: OneAttackStats::new(),
             oh_white: OneAttackStats::new(),
-            proccs: HashMap::new()
+            extra_attacks: OneAttackStats::new(),
+            proccs: HashMap::new(),
+            buff_uptime: HashMap::new(),
+            cd_activations: HashMap::new(),
+            energy_wasted: HashMap::new(),
+            time_at_energy_cap: 0.0,
+            energy_time_integral: 0.0,
+            combo_points_wasted: 0,
+            now: 0.0,
+            timeline_bin_width: 0.0,
+            timeline: Vec::new()
         }
     }
 
@@ -191,17 +678,33 @@ impl CurrentStats {
             backstab: self.backstab.clone(),
             sinister_strike: self.sinister_strike.clone(),
             eviscerate: self.eviscerate.clone(),
+            slice_and_dice: self.slice_and_dice.clone(),
             mh_white: self.mh_white.clone(),
             oh_white: self.oh_white.clone(),
-            proccs: self.proccs.clone()
+            extra_attacks: self.extra_attacks.clone(),
+            proccs: self.proccs.clone(),
+            buff_uptime: self.buff_uptime.clone(),
+            cd_activations: self.cd_activations.clone(),
+            energy_wasted: self.energy_wasted.clone(),
+            time_at_energy_cap: self.time_at_energy_cap,
+            energy_time_integral: self.energy_time_integral,
+            combo_points_wasted: self.combo_points_wasted,
+            now: self.now,
+            timeline_bin_width: self.timeline_bin_width,
+            timeline: self.timeline.clone()
         }
     }
 
-    pub fn declare_proccs(&mut self, hit_proccs: &Vec<HitProcc>) {
+    pub fn declare_proccs(&mut self, hit_proccs: &[HitProcc],
+                          slot: WeaponSlot) {
         for i in 0..hit_proccs.len() {
             let name = match &hit_proccs[i] {
                 HitProcc::Dmg(name,_,_,_) => name.clone(),
-                HitProcc::Strength(name,_,_,_) => name.clone(),
+                HitProcc::Strength(name,_,_,_) => {
+                    self.declare_buff_uptime(
+                        &get_stat_buff_uptime_name(name, slot));
+                    name.clone()
+                },
                 HitProcc::ExtraAttack(name,_) => name.clone(),
                 HitProcc::None => panic!("Simulation does not run with \
                 'None' proccs"),
@@ -211,34 +714,191 @@ impl CurrentStats {
         }
     }
 
+    fn add(&mut self, other: &CurrentStats) {
+        self.dmg += other.dmg;
+        self.backstab.add(&other.backstab);
+        self.sinister_strike.add(&other.sinister_strike);
+        self.eviscerate.add(&other.eviscerate);
+        self.slice_and_dice.add(&other.slice_and_dice);
+        self.mh_white.add(&other.mh_white);
+        self.oh_white.add(&other.oh_white);
+        self.extra_attacks.add(&other.extra_attacks);
+        for (name, dmg_and_count) in &other.proccs {
+            let cur_val = self.proccs.entry(name.to_string())
+                .or_insert(DamageAndCount::new());
+            cur_val.dmg += dmg_and_count.dmg;
+            cur_val.count += dmg_and_count.count;
+        }
+        for (name, uptime) in &other.buff_uptime {
+            let cur_val = self.buff_uptime.entry(name.to_string())
+                .or_insert(0.0);
+            *cur_val += uptime;
+        }
+        for (name, count) in &other.cd_activations {
+            *self.cd_activations.entry(name.to_string()).or_insert(0) += count;
+        }
+        for (name, wasted) in &other.energy_wasted {
+            *self.energy_wasted.entry(name.to_string()).or_insert(0) += wasted;
+        }
+        self.time_at_energy_cap += other.time_at_energy_cap;
+        self.energy_time_integral += other.energy_time_integral;
+        self.combo_points_wasted += other.combo_points_wasted;
+        add_timeline(&mut self.timeline, &other.timeline);
+    }
+
+    fn get_ability_reports(&self, minutes: f32) -> Vec<AbilityReport> {
+        // minutes fought over all the iterations summed up here
+        let attacks = vec![
+            ("Backstab", &self.backstab),
+            ("Sinister strike", &self.sinister_strike),
+            ("Eviscerate", &self.eviscerate),
+            ("Slice and dice", &self.slice_and_dice),
+            ("MH white", &self.mh_white),
+            ("OH white", &self.oh_white),
+            ("MH extra attacks", &self.extra_attacks)
+        ];
+        let mut reports = Vec::new();
+        for (name, attack) in attacks {
+            if attack.tot_count > 0 {
+                reports.push(attack.get_report(name, minutes));
+            }
+        }
+        return reports;
+    }
+
+    pub fn declare_buff_uptime(&mut self, name: &str) {
+        // declared buffs get an entry every fight, even when never active
+        self.buff_uptime.insert(name.to_string(), 0.0);
+        for bin in self.timeline.iter_mut() {
+            bin.buff_uptime.insert(name.to_string(), 0.0);
+        }
+    }
+
+    pub fn declare_cooldown(&mut self, name: &str, has_duration: bool) {
+        self.cd_activations.insert(name.to_string(), 0);
+        if has_duration { self.declare_buff_uptime(name); }
+    }
+
+    pub fn record_cooldown_activation(&mut self, name: &str) {
+        *self.cd_activations.entry(name.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn declare_energy_source(&mut self, name: &str) {
+        self.energy_wasted.insert(name.to_string(), 0);
+    }
+
+    pub fn record_energy_wasted(&mut self, source: &str, energy: i32) {
+        *self.energy_wasted.entry(source.to_string()).or_insert(0) += energy;
+    }
+
+    pub fn record_energy_level(&mut self, energy: i32, duration: f32, 
+                               at_cap: bool) {
+        self.energy_time_integral += energy as f32 * duration;
+        if at_cap { self.time_at_energy_cap += duration; }
+
+        for (i, overlap) in self.get_bin_overlaps(self.now - duration, 
+                                                  self.now) {
+            self.timeline[i].energy_integral += energy as f32 * overlap;
+        }
+    }
+
+    pub fn record_combo_point_wasted(&mut self) {
+        self.combo_points_wasted += 1;
+    }
+
     pub fn set_fight_length(&mut self, fight_length: f32) {
         self.fight_length = fight_length;
     }
 
-    pub fn record_mh_white_dmg_and_hit(&mut self, dmg: f32, hit_type: &Hit) {
+    pub fn set_timeline_bin_width(&mut self, bin_width: f32) {
+        self.timeline_bin_width = bin_width;
+        self.timeline.clear();
+        if bin_width <= 0.0 { return; }
+        let n_bins = (self.fight_length / bin_width).ceil() as usize;
+        for _ in 0..n_bins { self.timeline.push(TimelineBin::new()); }
+    }
+
+    pub fn set_time(&mut self, now: f32) {
+        self.now = now;
+    }
+
+    fn get_bin_overlaps(&self, start: f32, end: f32) -> Vec<(usize, f32)> {
+        // the bins touched by the interval, and for how long
+        let mut overlaps = Vec::new();
+        if self.timeline.is_empty() || end <= start { return overlaps; }
+        let first = (start / self.timeline_bin_width) as usize;
+        for i in first..self.timeline.len() {
+            let bin_start = i as f32 * self.timeline_bin_width;
+            if bin_start >= end { break; }
+            let bin_end = bin_start + self.timeline_bin_width;
+            let overlap = end.min(bin_end) - start.max(bin_start);
+            if overlap > 0.0 { overlaps.push((i, overlap)); }
+        }
+        return overlaps;
+    }
+
+    fn add_dmg(&mut self, dmg: f32) {
         self.dmg += dmg;
+        if self.timeline.is_empty() { return; }
+        let mut bin = (self.now / self.timeline_bin_width) as usize;
+        if bin >= self.timeline.len() { bin = self.timeline.len() - 1; }
+        self.timeline[bin].dmg += dmg;
+    }
+
+    pub fn record_mh_white_dmg_and_hit(&mut self, dmg: f32, hit_type: &Hit) {
+        self.add_dmg(dmg);
         self.mh_white.add_dmg_and_hit(dmg, hit_type);
     }
 
     pub fn record_oh_white_dmg_and_hit(&mut self, dmg: f32, hit_type: &Hit) {
-        self.dmg += dmg;
+        self.add_dmg(dmg);
         self.oh_white.add_dmg_and_hit(dmg, hit_type);
     }
 
-    pub fn record_sinister_strike_dmg_and_hit(&mut self, dmg: f32, hit_type: &Hit)
-    {
-        self.dmg += dmg;
+    pub fn record_sinister_strike_dmg_and_hit(&mut self, dmg: f32, hit_type: &Hit,
+                                              energy: i32) {
+        self.add_dmg(dmg);
         self.sinister_strike.add_dmg_and_hit(dmg, hit_type);
+        self.sinister_strike.energy += energy;
     }
     
-    pub fn record_backstab_dmg_and_hit(&mut self, dmg: f32, hit_type: &Hit) {
-        self.dmg += dmg;
+    pub fn record_backstab_dmg_and_hit(&mut self, dmg: f32, hit_type: &Hit,
+                                       energy: i32) {
+        self.add_dmg(dmg);
         self.backstab.add_dmg_and_hit(dmg, hit_type);
+        self.backstab.energy += energy;
     }
 
-    pub fn record_eviscerate_dmg_and_hit(&mut self, dmg: f32, hit_type: &Hit) {
-        self.dmg += dmg;
+    pub fn record_eviscerate_dmg_and_hit(&mut self, dmg: f32, hit_type: &Hit,
+                                         energy: i32) {
+        self.add_dmg(dmg);
         self.eviscerate.add_dmg_and_hit(dmg, hit_type);
+        self.eviscerate.energy += energy;
+    }
+
+    pub fn record_slice_and_dice(&mut self, energy: i32) {
+        self.slice_and_dice.add_dmg_and_hit(0.0, &Hit::Hit);
+        self.slice_and_dice.energy += energy;
+    }
+
+    pub fn record_extra_attack_dmg_and_hit(&mut self, name: &str, dmg: f32,
+                                           hit_type: &Hit) {
+        self.add_dmg(dmg);
+        let cur_val = self.proccs.entry(name.to_string())
+            .or_insert(DamageAndCount::new());
+        cur_val.dmg += dmg;
+        self.extra_attacks.add_dmg_and_hit(dmg, hit_type);
+    }
+
+    pub fn record_buff_uptime(&mut self, name: &str, uptime: f32) {
+        // the buff was active during the uptime that just ended
+        let cur_val = self.buff_uptime.entry(name.to_string()).or_insert(0.0);
+        *cur_val += uptime;
+
+        for (i, overlap) in self.get_bin_overlaps(self.now - uptime, self.now) {
+            *self.timeline[i].buff_uptime.entry(name.to_string())
+                .or_insert(0.0) += overlap;
+        }
     }
 
     pub fn record_procc(&mut self, procc: &HitProcc) {
@@ -248,11 +908,19 @@ impl CurrentStats {
                     .or_insert(DamageAndCount::new());
                 cur_val.count += 1;
                 cur_val.dmg += damage;
-                self.dmg += damage
+                self.add_dmg(*damage);
+                },
+            HitProcc::Strength(name,_,_,_) => {
+                let cur_val = self.proccs.entry(name.to_string())
+                    .or_insert(DamageAndCount::new());
+                cur_val.count += 1;
                 },
-            HitProcc::Strength(_,_,_,_) => (),
 
-            HitProcc::ExtraAttack(_,_) => (),
+            HitProcc::ExtraAttack(name,_) => {
+                let cur_val = self.proccs.entry(name.to_string())
+                    .or_insert(DamageAndCount::new());
+                cur_val.count += 1;
+                },
             HitProcc::None => panic!("HitProcc::None cannot procc!"),
         }
     }
@@ -280,6 +948,20 @@ impl CurrentStats {
                          100.0 * dmg_and_count.dmg / self.dmg);
             }
         }
+        for (name, uptime) in &self.buff_uptime {
+            println!("{} uptime:\t{:.2}%", name,
+                     100.0 * uptime / self.fight_length);
+        }
+        println!("Average energy:\t\t{:.1}", 
+                 self.energy_time_integral / self.fight_length);
+        println!("Time at energy cap:\t{:.2}%", 
+                 100.0 * self.time_at_energy_cap / self.fight_length);
+        for (name, wasted) in &self.energy_wasted {
+            if *wasted > 0 {
+                println!("Energy wasted ({}):\t{}", name, wasted);
+            }
+        }
+        println!("Combo points wasted:\t{}", self.combo_points_wasted);
         println!("\n");
     }
 
@@ -289,8 +971,10 @@ impl CurrentStats {
         self.backstab.print_with_name("Backstab");
         self.sinister_strike.print_with_name("Sinister strike");
         self.eviscerate.print_with_name("Eviscerate");
+        self.slice_and_dice.print_with_name("Slice and dice");
         self.mh_white.print_with_name("MH white");
         self.oh_white.print_with_name("OH white");
+        self.extra_attacks.print_with_name("MH extra attacks");
     }
      
     pub fn clear(&mut self) {
@@ -298,8 +982,10 @@ impl CurrentStats {
         self.backstab.clear();
         self.sinister_strike.clear();
         self.eviscerate.clear();
+        self.slice_and_dice.clear();
         self.mh_white.clear();
         self.oh_white.clear();
+        self.extra_attacks.clear();
         self.clear_proccs();
     }
 
@@ -309,30 +995,61 @@ impl CurrentStats {
             new_map.entry(name.to_string()).or_insert(DamageAndCount::new());
         }
         self.proccs = new_map;
+
+        for (_, uptime) in self.buff_uptime.iter_mut() {
+            *uptime = 0.0;
+        }
+        for (_, count) in self.cd_activations.iter_mut() {
+            *count = 0;
+        }
+        for (_, wasted) in self.energy_wasted.iter_mut() {
+            *wasted = 0;
+        }
+        self.time_at_energy_cap = 0.0;
+        self.energy_time_integral = 0.0;
+        self.combo_points_wasted = 0;
+        self.now = 0.0;
+        for bin in self.timeline.iter_mut() { bin.clear(); }
     }
 }
 
 #[derive(Clone,Debug)]
 struct OneAttackStats {
     dmg: f32,
+    min_dmg: f32,
+    max_dmg: f32,
+    dmg_count: i32, // attacks that did damage
+    hit_dmg: f32, // of the landed attacks that did not crit
+    crit_dmg: f32,
+    energy: i32,
     tot_count: i32,
     crit: i32,
     dodge: i32,
     glancing: i32,
     hit: i32,
-    miss: i32
+    miss: i32,
+    parry: i32,
+    block: i32
 }
     
 impl OneAttackStats {
     fn new() -> OneAttackStats {
         OneAttackStats {
             dmg: 0.0,
+            min_dmg: 0.0,
+            max_dmg: 0.0,
+            dmg_count: 0,
+            hit_dmg: 0.0,
+            crit_dmg: 0.0,
+            energy: 0,
             tot_count: 0,
             crit: 0,
             dodge: 0,
             glancing: 0,
             hit: 0,
-            miss: 0
+            miss: 0,
+            parry: 0,
+            block: 0
         }
     }
 
@@ -340,13 +1057,82 @@ impl OneAttackStats {
         self.tot_count += 1;
         self.dmg += dmg;
 
-        if *hit_type == Hit::Hit { self.hit += 1; }
-        else if *hit_type == Hit::Crit { self.crit += 1; }
-        else if *hit_type == Hit::Miss { self.miss += 1; }
-        else if *hit_type == Hit::Glancing { self.glancing += 1; }
-        else if *hit_type == Hit::Dodge { self.dodge += 1; }
-        else if *hit_type == Hit::Hit { self.hit += 1; }
-        else { panic!("Hit type not implemented: {}", *hit_type); }
+        if dmg > 0.0 {
+            if self.dmg_count == 0 || dmg < self.min_dmg { self.min_dmg = dmg; }
+            if dmg > self.max_dmg { self.max_dmg = dmg; }
+            self.dmg_count += 1;
+        }
+
+        match hit_type {
+            Hit::Hit => { self.hit += 1; self.hit_dmg += dmg; },
+            Hit::Crit => { self.crit += 1; self.crit_dmg += dmg; },
+            Hit::Glancing => { self.glancing += 1; self.hit_dmg += dmg; },
+            Hit::Block => { self.block += 1; self.hit_dmg += dmg; },
+            Hit::Miss => self.miss += 1,
+            Hit::Dodge => self.dodge += 1,
+            Hit::Parry => self.parry += 1
+        }
+    }
+
+    fn add(&mut self, other: &OneAttackStats) {
+        if other.dmg_count > 0 {
+            if self.dmg_count == 0 || other.min_dmg < self.min_dmg {
+                self.min_dmg = other.min_dmg;
+            }
+            if other.max_dmg > self.max_dmg { self.max_dmg = other.max_dmg; }
+        }
+        self.dmg += other.dmg;
+        self.dmg_count += other.dmg_count;
+        self.hit_dmg += other.hit_dmg;
+        self.crit_dmg += other.crit_dmg;
+        self.energy += other.energy;
+        self.tot_count += other.tot_count;
+        self.crit += other.crit;
+        self.dodge += other.dodge;
+        self.glancing += other.glancing;
+        self.hit += other.hit;
+        self.miss += other.miss;
+        self.parry += other.parry;
+        self.block += other.block;
+    }
+
+    fn get_ratio(numerator: f32, denominator: f32) -> f32 {
+        if denominator > 0.0 { return numerator / denominator; }
+        else { return 0.0; }
+    }
+
+    fn get_report(&self, name: &str, minutes: f32) -> AbilityReport {
+        let count = self.tot_count as f32;
+        AbilityReport {
+            name: name.to_string(),
+            total_count: self.tot_count,
+            total_dmg: self.dmg,
+            min_dmg: self.min_dmg,
+            avg_dmg: OneAttackStats::get_ratio(self.dmg, 
+                                               self.dmg_count as f32),
+            max_dmg: self.max_dmg,
+            casts_per_minute: OneAttackStats::get_ratio(count, minutes),
+            avg_hit_dmg: OneAttackStats::get_ratio(self.hit_dmg, 
+                (self.hit + self.glancing + self.block) as f32),
+            avg_crit_dmg: OneAttackStats::get_ratio(self.crit_dmg, 
+                                                    self.crit as f32),
+            crit_rate: OneAttackStats::get_ratio(self.crit as f32, count),
+            miss_rate: OneAttackStats::get_ratio(self.miss as f32, count),
+            dodge_rate: OneAttackStats::get_ratio(self.dodge as f32, count),
+            parry_rate: OneAttackStats::get_ratio(self.parry as f32, count),
+            energy_spent: self.energy,
+            dmg_per_energy: OneAttackStats::get_ratio(self.dmg, 
+                                                      self.energy as f32),
+            hit_types: HitTypeCounts {
+                hit: self.hit,
+                crit: self.crit,
+                glancing: self.glancing,
+                miss: self.miss,
+                dodge: self.dodge,
+                parry: self.parry,
+                block: self.block
+            }
+        }
     }
 
     fn print_with_name(&self, name: &str) {
@@ -354,6 +1140,11 @@ impl OneAttackStats {
             println!("{}", name);
             println!("\tTotal hits: \t{}", self.tot_count); 
         }
+        if self.dmg_count > 0 {
+            println!("\tDamage: \tmin {:.0}, avg {:.0}, max {:.0}",
+                     self.min_dmg, self.dmg / self.dmg_count as f32,
+                     self.max_dmg);
+        }
         if self.hit > 0 { 
             println!("\tHit: \t\t{}\t{:.1}%", self.hit, 
                      100.0 * self.hit as f32 / self.tot_count as f32); 
@@ -374,16 +1165,63 @@ impl OneAttackStats {
             println!("\tDodge: \t\t{}\t{:.1}%", self.dodge, 
                      100.0 * self.dodge as f32 / self.tot_count as f32); 
         }
+        if self.parry > 0 {
+            println!("\tParry: \t\t{}\t{:.1}%", self.parry,
+                     100.0 * self.parry as f32 / self.tot_count as f32);
+        }
+        if self.block > 0 {
+            println!("\tBlock: \t\t{}\t{:.1}%", self.block,
+                     100.0 * self.block as f32 / self.tot_count as f32);
+        }
     }
 
     fn clear(&mut self) {
         self.dmg = 0.0;
+        self.min_dmg = 0.0;
+        self.max_dmg = 0.0;
+        self.dmg_count = 0;
+        self.hit_dmg = 0.0;
+        self.crit_dmg = 0.0;
+        self.energy = 0;
         self.tot_count = 0;
         self.crit = 0;
         self.dodge = 0;
         self.glancing = 0;
         self.hit = 0;
         self.miss = 0;
+        self.parry = 0;
+        self.block = 0;
+    }
+}
+
+#[derive(Clone,Debug)]
+struct TimelineBin {
+    dmg: f32,
+    energy_integral: f32, // energy times the time it was held
+    buff_uptime: HashMap<String,f32>
+}
+
+impl TimelineBin {
+    fn new() -> TimelineBin {
+        TimelineBin {
+            dmg: 0.0,
+            energy_integral: 0.0,
+            buff_uptime: HashMap::new()
+        }
+    }
+
+    fn add(&mut self, other: &TimelineBin) {
+        self.dmg += other.dmg;
+        self.energy_integral += other.energy_integral;
+        for (name, uptime) in &other.buff_uptime {
+            *self.buff_uptime.entry(name.to_string()).or_insert(0.0) += uptime;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.dmg = 0.0;
+        self.energy_integral = 0.0;
+        for (_, uptime) in self.buff_uptime.iter_mut() { *uptime = 0.0; }
     }
 }
 
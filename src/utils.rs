@@ -1,5 +1,11 @@
-use clap::{Arg, App};
-use rand::distributions::{Distribution, Uniform};
+use clap::{Arg, App, AppSettings, SubCommand};
+use crate::combat_log;
+use crate::output::OutputFormat;
+use rand::Rng;
+use rand::rngs::StdRng;
+
+// seconds per timeline bin in the html report when no width is given
+const REPORT_TIMELINE_BIN_WIDTH: &str = "5";
 
 extern crate serde;
 extern crate serde_yaml;
@@ -9,11 +15,6 @@ pub fn deb<T: std::fmt::Debug>(x: T) {
     println!("{:?}", x);
 }
 
-pub fn min_f32(x: f32, y: f32) -> f32 {
-    if x <= y { return x; }
-    else { return y; }
-}
-
 pub fn max_f32(x: f32, y: f32) -> f32 {
     if x >= y { return x; }
     else { return y; }
@@ -29,26 +30,54 @@ pub fn max_i32(x: i32, y: i32) -> i32 {
     else { return y; }
 }
 
-#[derive(Debug)]
+#[derive(Clone,Debug)]
 pub struct Args {
-    pub dt: f32,
+    pub command: String,
+    pub compare_files: Vec<String>,
     pub enemy_lvl: i32,
     pub fight_length: f32,
+    pub csv_file: String,
     pub iterations: i32,
+    pub log_file: String,
+    pub log_filter: Vec<String>,
+    pub log_format: String,
+    pub output: OutputFormat,
+    pub precision: f32,
+    pub report_file: String,
+    pub save_file: String,
+    pub seed: u64,
     pub spec_file: String,
+    pub threads: i32,
+    pub timeline_bin_width: f32,
+    pub timeline_csv_file: String,
+    pub top_candidates: i32,
     pub verb: i32,
     pub weight_mult: i32,
     pub weights: bool
 }
 
 impl Args {
-    fn default_args() -> Args {
+    pub fn default_args() -> Args {
         Args {
-            dt: 0.0,
+            command: "".to_string(),
+            compare_files: Vec::new(),
             enemy_lvl: 0,
             fight_length: 0.0,
+            csv_file: "".to_string(),
             iterations: 0,
+            log_file: "".to_string(),
+            log_filter: Vec::new(),
+            log_format: "".to_string(),
+            output: OutputFormat::Text,
+            precision: 0.0,
+            report_file: "".to_string(),
+            save_file: "".to_string(),
+            seed: 0,
             spec_file: "".to_string(),
+            threads: 1,
+            timeline_bin_width: 0.0,
+            timeline_csv_file: "".to_string(),
+            top_candidates: 0,
             verb: 0,
             weight_mult: 0,
             weights: false
@@ -56,45 +85,117 @@ impl Args {
     }
 }
 
-pub fn get_arguments() -> Args {
+fn get_spec_file_arg<'a, 'b>() -> Arg<'a, 'b> {
+    return Arg::with_name("Specs file") 
+        .required(true)
+        .short("f") 
+        .long("file").takes_value(true) 
+        .help("Parameter file that specifies entire character.");
+}
 
-    let matches = App::new("WoW rogue simulator") 
-        .version("0.1.0") 
-        .author("Magnus Lindström <magnus.lindstrom@tuta.io>")
-        .about("Compares items/specs for PvE raiding purposes. Combat Rogues.") 
-        .arg(Arg::with_name("Step length") 
-             .required(false)
-             .short("s") 
-             .long("step").takes_value(true) 
-             .help("The time step. Default is 0.01s."))
-        .arg(Arg::with_name("Specs file") 
-             .required(true)
-             .short("f") 
-             .long("file").takes_value(true) 
-             .help("Parameter file that specifies entire character."))
-        .arg(Arg::with_name("Nr of iterations") 
+fn get_optimizer_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    return vec![
+        Arg::with_name("Candidates") 
+            .long("top").takes_value(true) 
+            .help("Number of the best estimated candidates that are fully \
+                  simulated. Default is 10."),
+        get_save_file_arg()
+    ];
+}
+
+fn get_save_file_arg<'a, 'b>() -> Arg<'a, 'b> {
+    return Arg::with_name("Save file") 
+        .long("save").takes_value(true) 
+        .help("Write the best spec to this file. Default is \
+              optimized.yaml.");
+}
+
+fn get_common_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    // options shared by the plain simulation and the commands
+    return vec![
+        Arg::with_name("Nr of iterations") 
              .short("i") 
              .long("iterations").takes_value(true) 
-             .help("Number of iterations to average over. Default is 10 000"))
-        .arg(Arg::with_name("Fight length") 
+             .help("Number of iterations to average over. Default is 10 000"),
+        Arg::with_name("Fight length") 
              .short("t") 
              .long("time").takes_value(true) 
-             .help("Seconds of duration per fight. Default is 60s."))
-        .arg(Arg::with_name("Enemy level") 
+             .help("Seconds of duration per fight. Default is 60s."),
+        Arg::with_name("Enemy level") 
              .short("e") 
              .long("enemy_lvl").takes_value(true) 
-             .help("Lvl of the enemy. Default is 63."))
-        .arg(Arg::with_name("Weights") 
+             .validator(is_positive_level)
+             .help("Lvl of the enemy. Overrides the level of the target \
+                   in the specs file, which defaults to 63."),
+        Arg::with_name("Weights") 
             .short("w") 
             .long("weights") 
             .takes_value(false) 
-            .help("Permute stats/talents slightly to get delta dps values."))
-        .arg(Arg::with_name("Weight multiplier") 
+            .help("Permute stats/talents slightly to get delta dps values."),
+        Arg::with_name("Weight multiplier") 
             .short("m") 
             .long("weight_mult") 
             .takes_value(true) 
-            .help("Change degree of permutation by a factor."))
-        .arg(Arg::with_name("Verbose") 
+            .help("Change degree of permutation by a factor."),
+        Arg::with_name("Output format") 
+             .short("o") 
+             .long("output").takes_value(true) 
+             .possible_values(&["text", "json"])
+             .help("Format of the results. 'json' prints one document with \
+                   the schema described in src/output.rs. Default is text."),
+        Arg::with_name("Csv file") 
+             .long("csv").takes_value(true) 
+             .help("Write the dps and ability breakdown of every iteration \
+                   to this csv file."),
+        Arg::with_name("Timeline") 
+             .long("timeline").takes_value(true) 
+             .help("Record damage, energy and buffs in time bins of this \
+                   many seconds and print them averaged over all iterations."),
+        Arg::with_name("Timeline csv file") 
+             .long("timeline_csv").takes_value(true) 
+             .requires("Timeline")
+             .help("Write the timeline to this csv file."),
+        Arg::with_name("Report file") 
+             .long("report").takes_value(true) 
+             .help("Write a self-contained html report with charts of the \
+                   results to this file. Records a timeline in bins of \
+                   5s unless --timeline is given."),
+        Arg::with_name("Log format") 
+             .long("log").takes_value(true) 
+             .possible_values(&["text", "jsonl"])
+             .help("Print a combat log of every iteration. 'jsonl' writes one \
+                   JSON object per event. Verbose runs print the text log. \
+                   The commands and --weights never log."),
+        Arg::with_name("Log file") 
+             .long("log_file").takes_value(true) 
+             .help("Write the combat log to this file instead of stdout, or \
+                   of stderr with --output json."),
+        Arg::with_name("Log filter") 
+             .long("log_filter").takes_value(true) 
+             .use_delimiter(true)
+             .possible_values(&combat_log::LOG_CATEGORIES)
+             .help("Comma separated event categories to keep in the combat \
+                   log: swing, ability, procc, energy, combo_points, buff \
+                   and cooldown. Default is all of them."),
+        Arg::with_name("Precision") 
+             .short("p") 
+             .long("precision").takes_value(true) 
+             .help("Keep iterating until the 95% confidence interval of the \
+                   mean dps, or of every stat weight delta, is within ± this \
+                   many dps. Overrides the number of iterations."),
+        Arg::with_name("Seed") 
+            .long("seed") 
+            .takes_value(true) 
+            .help("Seed for the random number generator. The same seed and \
+                  settings always give the same result. Default is a random \
+                  seed, which is printed with the results."),
+        Arg::with_name("Threads") 
+            .long("threads") 
+            .takes_value(true) 
+            .help("Number of worker threads to share the iterations. Default \
+                  is the number of available cores. Verbose runs always use \
+                  a single thread to keep the combat log readable."),
+        Arg::with_name("Verbose") 
             .short("v") 
             .long("verbose") 
             .multiple(true)
@@ -104,40 +205,157 @@ pub fn get_arguments() -> Args {
             and strikes made\n\
             2: prints minor events like energy refill and a detailed summary\n\
             of all attacks made.\n\
-            3: dump entire simulator object at the end of a run."))
+            3: dump entire simulator object at the end of a run.")
+    ];
+}
+
+fn is_positive_level(level: String) -> Result<(), String> {
+    match level.parse::<i32>() {
+        Ok(level) if level > 0 => return Ok(()),
+        _ => return Err(format!("the enemy level has to be a positive \
+                                number, got {}", level))
+    }
+}
+
+pub fn get_arguments() -> Args {
+
+    let app_matches = App::new("WoW rogue simulator") 
+        .version("0.1.0") 
+        .author("Magnus Lindström <magnus.lindstrom@tuta.io>")
+        .about("Compares items/specs for PvE raiding purposes. Combat Rogues.") 
+        .arg(get_spec_file_arg())
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .args(&get_common_args())
+        .subcommand(SubCommand::with_name("compare")
+            .about("Simulates two or more specs files with the same random \
+                   numbers and tests if their dps differ from the first.")
+            .arg(Arg::with_name("Compared specs files")
+                 .required(true)
+                 .multiple(true)
+                 .min_values(2)
+                 .help("Specs files to compare, the first is the baseline."))
+            .args(&get_common_args()))
+        .subcommand(SubCommand::with_name("optimize")
+            .about("Searches the item database for the gear with the highest \
+                   dps, keeping the enchants, buffs and talents of the specs \
+                   file, and saves it as a new specs file.")
+            .arg(get_spec_file_arg())
+            .args(&get_optimizer_args())
+            .args(&get_common_args()))
+        .subcommand(SubCommand::with_name("upgrades")
+            .about("Swaps every item of the database into the gear of the \
+                   specs file, one at a time, and ranks the dps gains per \
+                   slot.")
+            .arg(get_spec_file_arg())
+            .args(&get_common_args()))
+        .subcommand(SubCommand::with_name("enchants")
+            .about("Tries every enchant and weapon consumable of the \
+                   database in each slot and hand, reports the dps of each \
+                   alternative and saves the best combination.")
+            .arg(get_spec_file_arg())
+            .arg(get_save_file_arg())
+            .args(&get_common_args()))
+        .subcommand(SubCommand::with_name("talents")
+            .about("Searches the legal talent builds within 51 points for \
+                   the highest dps, reports the dps of every talent point \
+                   and saves the best build as a new specs file.")
+            .arg(get_spec_file_arg())
+            .args(&get_optimizer_args())
+            .args(&get_common_args()))
         .get_matches();
 
-    let dt = matches.value_of("Step length").unwrap_or("0.01");
-    let spec_file = matches.value_of("Specs file").unwrap();
-    let iterations = matches.value_of("Nr of iterations").unwrap_or("1");
+    // the options of a command are given after its name
+    let mut command = "";
+    let mut compare_files: Vec<String> = Vec::new();
+    let mut matches = &app_matches;
+    if let Some(sub_matches) = app_matches.subcommand_matches("compare") {
+        command = "compare";
+        compare_files = sub_matches.values_of("Compared specs files").unwrap()
+            .map(|file| file.to_string()).collect();
+        matches = sub_matches;
+    }
+    for name in &["optimize", "upgrades", "enchants", "talents"] {
+        if let Some(sub_matches) = app_matches.subcommand_matches(name) {
+            command = name;
+            matches = sub_matches;
+        }
+    }
+
+    let spec_file = matches.value_of("Specs file").unwrap_or("");
+    // commands compare specs, which needs more than a single run
+    let default_iterations = if command.is_empty() { "1" } else { "10000" };
+    let iterations = matches.value_of("Nr of iterations")
+        .unwrap_or(default_iterations);
+    let top_candidates = matches.value_of("Candidates").unwrap_or("10");
+    let save_file = matches.value_of("Save file").unwrap_or("optimized.yaml");
     let fight_length = matches.value_of("Fight length").unwrap_or("60");
-    let enemy_lvl = matches.value_of("Enemy level").unwrap_or("63");
+    let enemy_lvl = matches.value_of("Enemy level").unwrap_or("0");
+    let precision = matches.value_of("Precision").unwrap_or("0");
+    let output = matches.value_of("Output format").unwrap_or("text");
+    let csv_file = matches.value_of("Csv file").unwrap_or("");
+    let report_file = matches.value_of("Report file").unwrap_or("");
+    let default_timeline = if report_file.is_empty() { "0" } 
+        else { REPORT_TIMELINE_BIN_WIDTH };
+    let timeline = matches.value_of("Timeline").unwrap_or(default_timeline);
+    let timeline_csv_file = matches.value_of("Timeline csv file")
+        .unwrap_or("");
+    let log_format = matches.value_of("Log format").unwrap_or("");
+    let log_file = matches.value_of("Log file").unwrap_or("");
     let weights = matches.is_present("Weights");
     let weight_mult = matches.value_of("Weight multiplier").unwrap_or("1");
     let verb = matches.occurrences_of("Verbose");
+    let available_threads = std::thread::available_parallelism()
+        .map(|n| n.get()).unwrap_or(1).to_string();
+    let threads = matches.value_of("Threads")
+        .unwrap_or(&available_threads);
 
     let mut args = Args::default_args();
-    args.dt = dt.parse().unwrap();
     args.spec_file = spec_file.to_string();
+    args.command = command.to_string();
+    args.compare_files = compare_files;
     args.verb = verb as i32;
     args.weights = weights;
     args.weight_mult = weight_mult.parse().unwrap();
     args.enemy_lvl = enemy_lvl.parse().unwrap();
     args.iterations = iterations.parse().unwrap();
+    args.precision = precision.parse().unwrap();
+    args.csv_file = csv_file.to_string();
+    args.report_file = report_file.to_string();
+    args.top_candidates = top_candidates.parse().unwrap();
+    args.save_file = save_file.to_string();
+    args.timeline_bin_width = timeline.parse().unwrap();
+    args.timeline_csv_file = timeline_csv_file.to_string();
+    args.log_format = log_format.to_string();
+    args.log_file = log_file.to_string();
+    args.log_filter = match matches.values_of("Log filter") {
+        Some(categories) => categories.map(|c| c.to_string()).collect(),
+        None => Vec::new()
+    };
+    args.output = match output {
+        "json" => OutputFormat::Json,
+        _ => OutputFormat::Text
+    };
+    args.threads = threads.parse().unwrap();
+    args.seed = match matches.value_of("Seed") {
+        Some(seed) => seed.parse().unwrap(),
+        None => rand::random()
+    };
+    // a single thread keeps the combat log in order
+    if combat_log::logging_is_enabled(&args) { args.threads = 1; }
     let fl: u32 = fight_length.parse().unwrap();
     args.fight_length = fl as f32;
 
     return args;
 }
 
-pub fn roll_die() -> f32 {
+pub fn roll_die(rng: &mut StdRng) -> f32 {
     // rolls a die between [0, 1)
-    
-    let mut rng = rand::thread_rng();
-    let roll_range = Uniform::from(100..10_000); // not including upper bound
-    let roll = roll_range.sample(&mut rng);
-    let roll: f32 = (roll as f32) / 10_000.0;
-    return roll;
+    return rng.gen::<f32>();
+}
+
+pub fn roll_between(rng: &mut StdRng, min: f32, max: f32) -> f32 {
+    // uniformly distributed value between min and max
+    return min + (max - min) * roll_die(rng);
 }
 
 pub fn mean(numbers: &Vec<f32>) -> f32 {
@@ -149,6 +367,30 @@ pub fn mean(numbers: &Vec<f32>) -> f32 {
     return avg as f32;
 }
 
+pub fn percentile(sorted_numbers: &[f32], fraction: f32) -> f32 {
+    // linear interpolation between the closest ranks, expects sorted input
+    if sorted_numbers.is_empty() { return 0.0; }
+    let rank = fraction * (sorted_numbers.len() - 1) as f32;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let weight = rank - lower as f32;
+    return sorted_numbers[lower] * (1.0 - weight) 
+        + sorted_numbers[upper] * weight;
+}
+
+pub fn print_table_row(widths: &[usize], cells: &[&str]) {
+    // the first cell is left aligned and the next ones right aligned to
+    // their widths, a last cell without a width is appended as free text
+    let mut row = format!("{:<w$}", cells[0], w = widths[0]);
+    for i in 1..cells.len() {
+        if i < widths.len() {
+            row += &format!("{:>w$}", cells[i], w = widths[i]);
+        }
+        else { row += &format!("  {}", cells[i]); }
+    }
+    println!("{}", row.trim_end());
+}
+
 pub fn std_dev(numbers: &Vec<f32>) -> f32 {
 
     let mean = mean(numbers);
@@ -159,3 +401,29 @@ pub fn std_dev(numbers: &Vec<f32>) -> f32 {
     let std_dev = (tmp / (numbers.len() - 1) as f32).sqrt();
     return std_dev;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile_hits_the_ends_and_the_median() {
+        let numbers = vec![1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(percentile(&numbers, 0.0), 1.0);
+        assert_eq!(percentile(&numbers, 0.5), 3.0);
+        assert_eq!(percentile(&numbers, 1.0), 5.0);
+    }
+
+    #[test]
+    fn percentile_interpolates_between_ranks() {
+        let numbers = vec![10.0, 20.0];
+        assert_eq!(percentile(&numbers, 0.25), 12.5);
+        assert_eq!(percentile(&[10.0, 20.0, 40.0], 0.75), 30.0);
+    }
+
+    #[test]
+    fn percentile_of_few_numbers() {
+        assert_eq!(percentile(&[], 0.5), 0.0);
+        assert_eq!(percentile(&[7.0], 0.95), 7.0);
+    }
+}
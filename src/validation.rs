@@ -0,0 +1,566 @@
+/* Validation of specs files, done before anything is simulated.
+ *
+ * Every problem of a specs file is collected, with the line it was found on,
+ * so that a config can be fixed in one go instead of one panic at a time.
+ */
+use crate::armory::{CharacterSpecification,Character,ItemCollection,
+EnchantType,Slot,TALENT_POINTS,TALENT_TREES,get_filler_points,
+get_searched_talents,get_talent_collection};
+
+use std::fs;
+
+// sections that fall back on defaults for the keys they leave out
+const OPTIONAL_SECTIONS: [&str; 1] = ["target"];
+// unknown names at most this far from a known one, or a third of their
+// length, get it suggested
+const SUGGESTION_DISTANCE: usize = 2;
+
+
+struct SpecLine {
+    number: usize,
+    section: String, // top level key the line belongs to
+    key: String,
+    value: String // empty for keys that open a list or mapping
+}
+
+struct SpecLines {
+    lines: Vec<SpecLine>
+}
+
+impl SpecLines {
+    fn new(spec_string: &str) -> SpecLines {
+        // a line based reading of the yaml, only to point at problems
+        let mut lines: Vec<SpecLine> = Vec::new();
+        let mut section = "".to_string();
+        let mut parent = "".to_string();
+        for (i, raw_line) in spec_string.lines().enumerate() {
+            let text = raw_line.split('#').next().unwrap_or("");
+            let trimmed = text.trim();
+            if trimmed.is_empty() || trimmed == "---" { continue; }
+
+            let (key, value) = if let Some(item) = trimmed.strip_prefix("- ") {
+                (parent.to_string(), item.trim().to_string())
+            } else if let Some(colon) = trimmed.find(':') {
+                let key = trimmed[..colon].trim().to_string();
+                let value = trimmed[colon + 1..].trim().to_string();
+                if value.is_empty() { parent = key.to_string(); }
+                if ! text.starts_with(' ') { section = key.to_string(); }
+                (key, value)
+            } else {
+                // a value on the line after its key
+                (parent.to_string(), trimmed.to_string())
+            };
+            lines.push(SpecLine { number: i + 1, section: section.to_string(),
+                                  key, value });
+        }
+        return SpecLines { lines };
+    }
+
+    fn get_line(&self, section: &str, key: &str, index: usize) -> usize {
+        // line of the index:th value of a key, else of the key itself, 0 if
+        // it can not be found
+        let matching: Vec<&SpecLine> = self.lines.iter()
+            .filter(|line| line.section == section && line.key == key)
+            .collect();
+        let values: Vec<&&SpecLine> = matching.iter()
+            .filter(|line| ! line.value.is_empty()).collect();
+        if index < values.len() { return values[index].number; }
+        return match matching.first() {
+            Some(line) => line.number,
+            None => 0
+        };
+    }
+}
+
+struct SpecCheck {
+    spec_file: String,
+    lines: SpecLines,
+    problems: Vec<(usize, String)> // line, 0 if unknown, and message
+}
+
+impl SpecCheck {
+    fn add(&mut self, section: &str, key: &str, index: usize, message: String) {
+        let line = self.lines.get_line(section, key, index);
+        self.problems.push((line, message));
+    }
+
+    fn get_report(&mut self) -> Vec<String> {
+        // in the order of the file
+        self.problems.sort_by_key(|(line, _)| *line);
+        return self.problems.iter().map(|(line, message)| if *line == 0 {
+            format!("{}: {}", self.spec_file, message)
+        } else {
+            format!("{}:{}: {}", self.spec_file, line, message)
+        }).collect();
+    }
+}
+
+fn get_edit_distance(a: &str, b: &str) -> usize {
+    // levenshtein distance, one row of the table at a time
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..b.len() + 1).collect();
+    for (i, char_a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for j in 0..b.len() {
+            let above = row[j + 1];
+            row[j + 1] = if char_a == b[j] { diagonal }
+                else { 1 + diagonal.min(row[j]).min(above) };
+            diagonal = above;
+        }
+    }
+    return row[b.len()];
+}
+
+fn get_suggestion<'a, I>(name: &str, known_names: I) -> String
+    where I: Iterator<Item = &'a String> {
+    // the closest known name, as the end of a problem message
+    let mut best: Option<(usize, &String)> = None;
+    for known in known_names {
+        let distance = get_edit_distance(name, known);
+        let is_better = match best {
+            Some((best_distance, best_name)) => distance < best_distance
+                || (distance == best_distance && known < best_name),
+            None => true
+        };
+        if is_better { best = Some((distance, known)); }
+    }
+    return match best {
+        Some((distance, known)) if distance
+            <= SUGGESTION_DISTANCE.max(name.chars().count() / 3) =>
+            format!(", did you mean {}?", known),
+        _ => "".to_string()
+    };
+}
+
+fn check_keys(check: &mut SpecCheck, path: &str, value: &mut serde_yaml::Value,
+              known: &serde_yaml::Value) {
+    // keys that the specs would silently ignore, and missing keys, which are
+    // filled in with their defaults so that the other checks can go on
+    let known_mapping = match known.as_mapping() {
+        Some(known_mapping) => known_mapping,
+        None => return
+    };
+    let mapping = match value.as_mapping_mut() {
+        Some(mapping) => mapping,
+        None => return
+    };
+    let section = path.split('.').next().unwrap_or("");
+    let known_keys: Vec<String> = known_mapping.iter()
+        .filter_map(|(key, _)| key.as_str()).map(|key| key.to_string())
+        .collect();
+    for (key, sub_value) in mapping.iter_mut() {
+        let key = match key.as_str() {
+            Some(key) => key,
+            None => continue
+        };
+        let key_path = if path.is_empty() { key.to_string() }
+            else { format!("{}.{}", path, key) };
+        if let Some(known_value) = known_mapping.get(
+                &serde_yaml::Value::from(key)) {
+            check_keys(check, &key_path, sub_value, known_value);
+        } else if path.is_empty() {
+            // other top level keys are left to other tools
+        } else if section == "talents"
+            && get_talent_collection().contains_key(key) {
+            check.add(section, key, 0, format!(
+                    "{} is not simulated and can not be set, the rows it \
+                    unlocks are accounted for without it", key));
+        } else {
+            let suggestion = get_suggestion(key, known_keys.iter());
+            check.add(section, key, 0, format!("unknown key {} in {}{}", key,
+                                               path, suggestion));
+        }
+    }
+
+    for (key, known_value) in known_mapping {
+        if mapping.contains_key(key) { continue; }
+        let key = key.as_str().unwrap_or("");
+        let key_section = if path.is_empty() { key } else { section };
+        if ! OPTIONAL_SECTIONS.contains(&key_section) {
+            let parent = path.rsplit('.').next().unwrap_or("");
+            let message = if path.is_empty() {
+                format!("missing section {}", key)
+            } else {
+                format!("missing key {} in {}", key, path)
+            };
+            check.add(section, parent, 0, message);
+        }
+        mapping.insert(serde_yaml::Value::from(key), known_value.clone());
+    }
+}
+
+fn check_target(check: &mut SpecCheck, spec: &CharacterSpecification) {
+    if spec.target.level <= 0 {
+        check.add("target", "level", 0, format!(
+                "the target level is {}, it has to be positive",
+                spec.target.level));
+    }
+}
+
+fn check_weapons(check: &mut SpecCheck, spec: &CharacterSpecification,
+                 collection: &ItemCollection) {
+    let hands = [("mh_name", &spec.items.mh_name, Slot::MH, "main hand"),
+                 ("oh_name", &spec.items.oh_name, Slot::OH, "off hand")];
+    for (key, name, slot, hand) in hands.iter() {
+        match collection.weapons.get(*name) {
+            Some(weapon) => if ! weapon.fits_slot(*slot) {
+                check.add("items", key, 0, format!(
+                        "{} can not be wielded in the {}", name, hand));
+            },
+            None => if collection.armor.contains_key(*name) {
+                check.add("items", key, 0, format!(
+                        "{} is armor, not a weapon", name));
+            } else {
+                let suggestion = get_suggestion(
+                    name, collection.weapons.keys());
+                check.add("items", key, 0, format!("unknown weapon {}{}",
+                                                   name, suggestion));
+            }
+        }
+    }
+    if let Some(weapon) = collection.weapons.get(&spec.items.mh_name) {
+        if weapon.is_unique() && spec.items.mh_name == spec.items.oh_name {
+            check.add("items", "oh_name", 0, format!(
+                    "{} is unique and can only be wielded once",
+                    spec.items.mh_name));
+        }
+    }
+}
+
+fn get_slot_capacity(slot: Slot) -> usize {
+    if slot == Slot::Ring || slot == Slot::Trinket { return 2; }
+    return 1;
+}
+
+fn check_armor(check: &mut SpecCheck, spec: &CharacterSpecification,
+               collection: &ItemCollection) {
+    let mut worn: Vec<(Slot, Vec<String>)> = Vec::new();
+    for (i, name) in spec.items.armor_names.iter().enumerate() {
+        let armor = match collection.armor.get(name) {
+            Some(armor) => armor,
+            None => {
+                if collection.weapons.contains_key(name) {
+                    check.add("items", "armor_names", i, format!(
+                            "{} is a weapon, it goes in mh_name or oh_name",
+                            name));
+                } else {
+                    let suggestion = get_suggestion(
+                        name, collection.armor.keys());
+                    check.add("items", "armor_names", i, format!(
+                            "unknown armor {}{}", name, suggestion));
+                }
+                continue;
+            }
+        };
+        if armor.slot == Slot::MH || armor.slot == Slot::OH
+            || armor.slot == Slot::None {
+            check.add("items", "armor_names", i, format!(
+                    "{} has no armor slot ({:?})", name, armor.slot));
+            continue;
+        }
+        // rings and trinkets are unique, the other slots hold one item
+        if spec.items.armor_names[..i].contains(name) {
+            check.add("items", "armor_names", i, format!(
+                    "{} is unique and can only be worn once", name));
+            continue;
+        }
+        match worn.iter().position(|(slot, _)| *slot == armor.slot) {
+            Some(j) => worn[j].1.push(name.to_string()),
+            None => worn.push((armor.slot, vec![name.to_string()]))
+        }
+        let names = &worn.iter().find(|(slot, _)| *slot == armor.slot)
+            .unwrap().1;
+        if names.len() > get_slot_capacity(armor.slot) {
+            check.add("items", "armor_names", i, format!(
+                    "too many {:?} items ({}), at most {} can be worn",
+                    armor.slot, names.join(", "),
+                    get_slot_capacity(armor.slot)));
+        }
+    }
+}
+
+fn check_enchants(check: &mut SpecCheck, spec: &CharacterSpecification,
+                  collection: &ItemCollection) {
+    let enchants = Character::initialize_enchant_collection();
+    let worn_slots: Vec<Slot> = spec.items.armor_names.iter()
+        .filter_map(|name| collection.armor.get(name))
+        .map(|armor| armor.slot).collect();
+
+    // armor enchants with a single slot take it first
+    let mut order: Vec<usize> = (0..spec.enchants.armor_enchant_names.len())
+        .collect();
+    order.sort_by_key(|i| match enchants.get(
+            &spec.enchants.armor_enchant_names[*i]) {
+        Some(enchant) => enchant.slot.len(),
+        None => 0
+    });
+    let mut enchanted_slots: Vec<Slot> = Vec::new();
+    for i in order {
+        let name = &spec.enchants.armor_enchant_names[i];
+        let enchant = match enchants.get(name) {
+            Some(enchant) => enchant,
+            None => {
+                let suggestion = get_suggestion(name, enchants.keys());
+                check.add("enchants", "armor_enchant_names", i, format!(
+                        "unknown enchant {}{}", name, suggestion));
+                continue;
+            }
+        };
+        let slots: Vec<Slot> = enchant.slot.iter()
+            .filter(|slot| **slot != Slot::MH && **slot != Slot::OH)
+            .cloned().collect();
+        if slots.is_empty() {
+            check.add("enchants", "armor_enchant_names", i, format!(
+                    "{} is a weapon enchant, it goes in mh_enchant_names or \
+                    oh_enchant_names", name));
+            continue;
+        }
+        let get_slot_names = |slots: Vec<&Slot>| slots.iter()
+            .map(|slot| format!("{:?}", slot)).collect::<Vec<String>>()
+            .join(" or ");
+        let worn: Vec<&Slot> = slots.iter()
+            .filter(|slot| worn_slots.contains(slot)).collect();
+        match worn.iter().find(|slot| ! enchanted_slots.contains(slot)) {
+            Some(slot) => enchanted_slots.push(**slot),
+            None => if ! worn.is_empty() {
+                check.add("enchants", "armor_enchant_names", i, format!(
+                        "{} has no free slot, {} is already enchanted", name,
+                        get_slot_names(worn)));
+            } else {
+                check.add("enchants", "armor_enchant_names", i, format!(
+                        "{} needs an item worn in {}", name,
+                        get_slot_names(slots.iter().collect())));
+            }
+        }
+    }
+
+    let hands = [("mh_enchant_names", &spec.enchants.mh_enchant_names,
+                  Slot::MH, "main hand"),
+                 ("oh_enchant_names", &spec.enchants.oh_enchant_names,
+                  Slot::OH, "off hand")];
+    for (key, names, slot, hand) in hands.iter() {
+        // a permanent enchant and a temporary one per weapon
+        let mut applied: Vec<EnchantType> = Vec::new();
+        for (i, name) in names.iter().enumerate() {
+            let enchant = match enchants.get(name) {
+                Some(enchant) => enchant,
+                None => {
+                    let suggestion = get_suggestion(name, enchants.keys());
+                    check.add("enchants", key, i, format!(
+                            "unknown enchant {}{}", name, suggestion));
+                    continue;
+                }
+            };
+            if ! enchant.slot.contains(slot) {
+                check.add("enchants", key, i, format!(
+                        "{} can not be applied to the {} weapon", name,
+                        hand));
+            } else if applied.contains(&enchant.enchant_type) {
+                check.add("enchants", key, i, format!(
+                        "{} is a second {:?} enchant on the {} weapon", name,
+                        enchant.enchant_type, hand));
+            } else {
+                applied.push(enchant.enchant_type);
+            }
+        }
+    }
+}
+
+fn check_talents(check: &mut SpecCheck, spec: &CharacterSpecification) {
+    let talent_db = get_talent_collection();
+    let searched = get_searched_talents(&talent_db, &spec.talents);
+    let points: Vec<i32> = searched.iter()
+        .map(|key| spec.talents.get_points(key).unwrap()).collect();
+
+    let mut in_range = true;
+    for (i, key) in searched.iter().enumerate() {
+        let info = &talent_db[key];
+        if points[i] < 0 || points[i] > info.max_points {
+            check.add("talents", key, 0, format!(
+                    "{} has {} points, it takes 0 to {}", info.name,
+                    points[i], info.max_points));
+            in_range = false;
+        }
+    }
+    // the rules of the trees only make sense with points in range
+    if ! in_range { return; }
+
+    let mut total = points.iter().sum::<i32>();
+    for tree in TALENT_TREES.iter() {
+        match get_filler_points(tree, &searched, &points, &talent_db) {
+            Ok(filler) => total += filler,
+            Err(error) => check.add("talents", "talents", 0, error)
+        }
+    }
+    if total > TALENT_POINTS {
+        check.add("talents", "talents", 0, format!(
+                "the talents need {} points, including the talents that \
+                unlock their rows, but only {} are available", total,
+                TALENT_POINTS));
+    }
+}
+
+pub fn get_valid_spec(spec_file: &str)
+    -> Result<CharacterSpecification, Vec<String>> {
+    if spec_file.is_empty() {
+        return Err(vec!["no specs file given, set one with -f".to_string()]);
+    }
+    let spec_string = match fs::read_to_string(spec_file) {
+        Ok(spec_string) => spec_string,
+        Err(error) => return Err(vec![format!("{}: could not be read: {}",
+                                              spec_file, error)])
+    };
+    let mut raw: serde_yaml::Value = match serde_yaml::from_str(&spec_string) {
+        Ok(raw) => raw,
+        Err(error) => return Err(vec![format!("{}: {}", spec_file, error)])
+    };
+
+    let mut check = SpecCheck {
+        spec_file: spec_file.to_string(),
+        lines: SpecLines::new(&spec_string),
+        problems: Vec::new()
+    };
+    let known = serde_yaml::to_value(CharacterSpecification::new())
+        .expect("Could not serialize the default specs");
+    check_keys(&mut check, "", &mut raw, &known);
+
+    let spec: CharacterSpecification = match serde_yaml::from_value(raw) {
+        Ok(spec) => spec,
+        Err(error) => {
+            // the text of the file knows where a wrongly typed value is
+            let message = error.to_string();
+            let line = match serde_yaml::from_str::<CharacterSpecification>(
+                    &spec_string) {
+                Err(text_error) if text_error.to_string().contains(&message)
+                    => text_error.location().map_or(0, |location|
+                                                    location.line()),
+                _ => 0
+            };
+            check.problems.push((line, message));
+            return Err(check.get_report());
+        }
+    };
+    let collection = ItemCollection::initialize_item_collection();
+    check_target(&mut check, &spec);
+    check_weapons(&mut check, &spec, &collection);
+    check_armor(&mut check, &spec, &collection);
+    check_enchants(&mut check, &spec, &collection);
+    check_talents(&mut check, &spec);
+
+    if check.problems.is_empty() { return Ok(spec); }
+    return Err(check.get_report());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = "\
+items:
+  armor_names:
+    # head
+    - bloodfang_hood
+    - onyxia_tooth_pendant
+  mh_name:
+    gutgore_ripper
+  oh_name: distracting_dagger
+talents:
+  malice: 5 # crit
+";
+
+    fn get_names(names: &[&str]) -> Vec<String> {
+        return names.iter().map(|name| name.to_string()).collect();
+    }
+
+    fn get_problems(spec_string: &str, file_name: &str) -> Vec<String> {
+        let path = std::env::temp_dir().join(file_name);
+        fs::write(&path, spec_string).unwrap();
+        let result = get_valid_spec(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        return match result {
+            Ok(_) => Vec::new(),
+            Err(problems) => problems
+        };
+    }
+
+    #[test]
+    fn edit_distance_counts_single_edits() {
+        assert_eq!(get_edit_distance("malice", "malice"), 0);
+        assert_eq!(get_edit_distance("malise", "malice"), 1);
+        assert_eq!(get_edit_distance("agression", "aggression"), 1);
+        assert_eq!(get_edit_distance("", "bok"), 3);
+        assert_eq!(get_edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn close_names_are_suggested() {
+        let known = get_names(&["malice", "lethality", "precision"]);
+        assert_eq!(get_suggestion("malise", known.iter()),
+                   ", did you mean malice?");
+        assert_eq!(get_suggestion("letality", known.iter()),
+                   ", did you mean lethality?");
+    }
+
+    #[test]
+    fn distant_names_are_not_suggested() {
+        let known = get_names(&["malice", "lethality"]);
+        assert_eq!(get_suggestion("sword", known.iter()), "");
+        // two edits are always close enough, more only for long names
+        assert_eq!(get_suggestion("mlce", known.iter()),
+                   ", did you mean malice?");
+        assert_eq!(get_suggestion("xyz", get_names(&["bok"]).iter()), "");
+    }
+
+    #[test]
+    fn list_items_have_their_own_lines() {
+        let lines = SpecLines::new(SPEC);
+        assert_eq!(lines.get_line("items", "armor_names", 0), 4);
+        assert_eq!(lines.get_line("items", "armor_names", 1), 5);
+        // past the end of the list, the line of the key
+        assert_eq!(lines.get_line("items", "armor_names", 2), 2);
+    }
+
+    #[test]
+    fn values_are_found_inline_and_on_the_next_line() {
+        let lines = SpecLines::new(SPEC);
+        assert_eq!(lines.get_line("items", "mh_name", 0), 7);
+        assert_eq!(lines.get_line("items", "oh_name", 0), 8);
+        assert_eq!(lines.get_line("talents", "malice", 0), 10);
+        assert_eq!(lines.get_line("talents", "mh_name", 0), 0);
+    }
+
+    #[test]
+    fn repo_specs_are_valid() {
+        let spec_string = fs::read_to_string("configs/current.yaml").unwrap();
+        assert_eq!(get_problems(&spec_string, "wow_sim_valid.yaml"),
+                   Vec::<String>::new());
+    }
+
+    #[test]
+    fn all_problems_are_reported_in_file_order() {
+        let spec_string = fs::read_to_string("configs/current.yaml").unwrap()
+            .replace("- onyxia_tooth_pendant", "- onyxia_toth_pendant")
+            .replace("- hand_of_justice",
+                     "- hand_of_justice\n    - hand_of_justice")
+            .replace("relentless_strikes: 1", "relentless_strikes: 2");
+        let problems = get_problems(&spec_string, "wow_sim_invalid.yaml");
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].contains(
+                "unknown armor onyxia_toth_pendant, did you mean \
+                onyxia_tooth_pendant?"));
+        assert!(problems[1].contains(
+                "hand_of_justice is unique and can only be worn once"));
+        assert!(problems[2].contains("Relentless Strikes has 2 points"));
+    }
+
+    #[test]
+    fn target_level_has_to_be_positive() {
+        let spec_string = fs::read_to_string("configs/current.yaml").unwrap()
+            .replace("  level: 63", "  level: 0");
+        let problems = get_problems(&spec_string, "wow_sim_level.yaml");
+        assert_eq!(problems, vec![format!(
+                "{}:94: the target level is 0, it has to be positive",
+                std::env::temp_dir().join("wow_sim_level.yaml").display())]);
+    }
+}